@@ -1,15 +1,235 @@
+/// Where part of an expression was found in the input, in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    /// The first byte covered.
+    pub start: usize,
+
+    /// The byte after the last one covered.
+    pub end: usize,
+}
+
+impl Span {
+    /// Covers the bytes from the start up to, but not including, the end.
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The text of the input which this covers.
+    pub fn text<'a>(&self, input: &'a str) -> &'a str {
+        input.get(self.start..self.end).unwrap_or("")
+    }
+}
+
+/// An expression, as parsed from the input, i.e. `1d20 + 5`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr {
+    /// What the expression is.
+    pub kind: ExprKind,
+
+    /// Where the expression was parsed from. Expressions which weren't parsed
+    /// cover nothing at the start of the input.
+    pub span: Span,
+}
+
+/// Expressions may also be built without parsing them, from these, in which
+/// case they cover nothing.
+impl Expr {
+    /// An expression found at the span in the input.
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// A whole number, i.e. `5`.
+    pub fn integer(n: i64) -> Self {
+        Self::from(ExprKind::Integer(n))
+    }
+
+    /// Two expressions joined by an operator, i.e. `2d6`, or `1d20 + 5`.
+    pub fn operator(op: ExprOp, left: Expr, right: Expr) -> Self {
+        Self::from(ExprKind::Operator(op, box left, box right))
+    }
+
+    /// An operator in front of an expression, i.e. `-1d6`.
+    pub fn unary(op: UnaryOp, expr: Expr) -> Self {
+        Self::from(ExprKind::Unary(op, box expr))
+    }
+
+    /// A call to a function by name, i.e. `max(1d6, 1d8)`.
+    pub fn call(name: &str, args: Vec<Expr>) -> Self {
+        Self::from(ExprKind::Call(name.to_string(), args))
+    }
+
+    /// The sides of a Fudge die, to be rolled with [`ExprOp::Roll`].
+    pub fn fudge() -> Self {
+        Self::from(ExprKind::Fudge)
+    }
+
+    /// The sides of a die with custom faces, to be rolled with [`ExprOp::Roll`].
+    pub fn faces(faces: Vec<i64>) -> Self {
+        Self::from(ExprKind::Faces(faces))
+    }
+
+    /// The chance of the condition coming out as true, i.e. `P(1d20 >= 15)`.
+    /// It can't be used within another expression.
+    pub fn chance(condition: Expr) -> Self {
+        Self::from(ExprKind::Chance(box condition))
+    }
+}
+
+impl From<ExprKind> for Expr {
+    fn from(kind: ExprKind) -> Self {
+        Self::new(kind, Span::default())
+    }
+}
+
+/// What an expression is, without where it came from.
 #[derive(Clone, Debug, PartialEq)]
-pub enum Expr {
+pub enum ExprKind {
+    /// A whole number, i.e. `5`.
     Integer(i64),
+
+    /// Two expressions joined by an operator, i.e. `1d20 + 5`.
     Operator(ExprOp, Box<Expr>, Box<Expr>),
+
+    /// An operator in front of an expression, i.e. the `-` in `-1d6`.
+    Unary(UnaryOp, Box<Expr>),
+
+    /// A call to a function by name, i.e. `max(1, 1d4 - 1)`.
+    Call(String, Vec<Expr>),
+
+    /// The sides of a Fudge die, i.e. the `F` in `4dF`.
+    Fudge,
+
+    /// The sides of a die with custom faces, i.e. the `{1,1,2}` in `2d{1,1,2}`.
+    Faces(Vec<i64>),
+
+    /// The chance of an expression coming out as true, i.e. `P(1d20 + 5 >= 15)`.
+    /// These are only found at the top level, never inside another expression.
+    Chance(Box<Expr>),
 }
 
+/// An operator joining two expressions, such as the `d` in `2d6`.
 #[derive(Clone, Debug, PartialEq)]
 pub enum ExprOp {
+    /// `+`
     Add,
+
+    /// `-`
     Sub,
+
+    /// `*`
     Mult,
+
+    /// Rounds towards zero, i.e. `-7 / 2` is `-3`.
     Div,
+
+    /// Rounds down, i.e. `-7 // 2` is `-4`.
+    FloorDiv,
+
+    /// Rounds up, i.e. `7 /^ 2` is `4`.
+    CeilDiv,
+
+    /// Rounds to the nearest, with halves away from zero, i.e. `7 /~ 2` is `4`.
+    RoundDiv,
+
+    /// What's left over from rounding down, so it has the sign of the right,
+    /// i.e. `-7 % 2` is `1`.
+    Mod,
+
+    /// Raises the left to the power of the right, i.e. `2 ^ 3` is `8`.
     Pow,
-    Roll,
+
+    /// Rolls the left number of dice with the right sides, and then applies
+    /// each modifier in turn, i.e. `4d6kh3`.
+    Roll(Vec<RollModifier>),
+
+    /// Comes out as `1` when true, and `0` when false.
+    Compare(Comparison),
+}
+
+/// An operator in front of an expression, such as the `-` in `-1d6`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnaryOp {
+    /// `-`
+    Neg,
+
+    /// `+`, which leaves the expression as it is.
+    Pos,
+}
+
+/// How the two sides of a comparison, such as the `>=` in `1d20 >= 15`, are compared.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Comparison {
+    /// `=` or `==`
+    Equal,
+
+    /// `!=`
+    NotEqual,
+
+    /// `<`
+    Less,
+
+    /// `<=`
+    LessOrEqual,
+
+    /// `>`
+    Greater,
+
+    /// `>=`
+    GreaterOrEqual,
+}
+
+/// Changes how the dice of a roll are rolled or counted, such as the `kh3` in `4d6kh3`.
+#[derive(Clone, Debug, PartialEq)]
+///
+/// Those with an optional condition apply to the highest face when exploding,
+/// and the lowest when rerolling, unless given one.
+pub enum RollModifier {
+    /// Keeps the highest number of dice, i.e. `kh3` or `k3`.
+    KeepHighest(i64),
+
+    /// Keeps the lowest number of dice, i.e. `kl1`.
+    KeepLowest(i64),
+
+    /// Drops the highest number of dice, i.e. `dh1`.
+    DropHighest(i64),
+
+    /// Drops the lowest number of dice, i.e. `dl1`.
+    DropLowest(i64),
+
+    /// Rolls another die for each which matches, i.e. `!` or `!>5`.
+    Explode(Option<Condition>),
+
+    /// Like exploding, but adds the extra rolls to the die which matched, i.e. `!!`.
+    Compound(Option<Condition>),
+
+    /// Like exploding, but takes one from each extra roll, i.e. `!p`.
+    Penetrate(Option<Condition>),
+
+    /// Rolls each die which matches again, until it doesn't, i.e. `r<2`.
+    Reroll(Option<Condition>),
+
+    /// Rolls each die which matches again, only once, i.e. `ro<2`.
+    RerollOnce(Option<Condition>),
+
+    /// Counts the dice which match as successes, rather than adding them
+    /// up, i.e. `>=5`.
+    Success(Condition),
+
+    /// Takes away a success for each die which matches, i.e. `f1`.
+    Failure(Condition),
+}
+
+/// Which faces of a die a modifier applies to. As on Roll20, `>N` and `<N`
+/// include `N` itself.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    /// `N` or `=N`
+    Equal(i64),
+
+    /// `>N` or `>=N`
+    AtLeast(i64),
+
+    /// `<N` or `<=N`
+    AtMost(i64),
 }
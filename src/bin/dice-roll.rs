@@ -7,10 +7,24 @@ use ::dice_roll;
 
 fn main() -> io::Result<()> {
     let username = "You";
-    let input = env::args().skip(1).collect::<Vec<String>>().join(" ");
+    let args = env::args().skip(1).collect::<Vec<String>>();
     let mut stdout = io::stdout();
 
-    dice_roll::main(&username, &input, &mut stdout)?;
+    match args.split_first() {
+        Some((flag, rest)) if flag == "--odds" => {
+            dice_roll::odds(username, &rest.join(" "), &mut stdout)?
+        }
+        Some((flag, rest)) if flag == "--hist" => {
+            dice_roll::histogram(username, &rest.join(" "), &mut stdout)?
+        }
+        Some((flag, rest)) if flag == "--stats" => {
+            dice_roll::stats(username, &rest.join(" "), &mut stdout)?
+        }
+        Some((command, [left, right])) if command == "compare" => {
+            dice_roll::compare(username, left, right, &mut stdout)?
+        }
+        _ => dice_roll::main(username, &args.join(" "), &mut stdout)?,
+    }
 
     Ok(())
 }
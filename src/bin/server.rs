@@ -2,6 +2,7 @@
 #![feature(proc_macro_hygiene, decl_macro)]
 
 use ::dice_roll;
+use ::dice_roll::{Limits, Markup};
 use ::rocket;
 use ::rocket::config::{Config, Environment};
 use ::rocket::request::{LenientForm, FromForm};
@@ -9,10 +10,15 @@ use ::rocket::{get, post, routes};
 use ::rocket_contrib::json::Json;
 use ::serde::Serialize;
 use ::std::env;
+use ::std::io::Write;
 
 const DEFAULT_PORT: u16 = 80;
 const DEFAULT_IP: &'static str = "0.0.0.0";
 
+/// Input is checked against this before it's parsed, as the limits on
+/// rolling only apply once it has been.
+const MAX_INPUT_LENGTH: usize = 500;
+
 fn main() {
     let address = get_address();
     println!("Running on ... {}", address);
@@ -27,7 +33,7 @@ fn roll_get(input: String) -> String {
     let username = "You";
     let mut response: Vec<u8> = Vec::new();
 
-    dice_roll::main(&username, &input, &mut response);
+    respond(username, &input, &mut response);
 
     String::from_utf8(response).unwrap()
 }
@@ -40,7 +46,7 @@ fn roll_get(input: String) -> String {
 fn roll_post(command: LenientForm<SlackCommand>) -> Json<SlackResponse> {
     let response_name = format!("<@{}>", command.user_id);
     let mut response: Vec<u8> = Vec::new();
-    dice_roll::main(&response_name, &command.text, &mut response);
+    respond(&response_name, &command.text, &mut response);
 
     Json(SlackResponse {
         response_type: SlackResponseType::InChannel,
@@ -48,6 +54,38 @@ fn roll_post(command: LenientForm<SlackCommand>) -> Json<SlackResponse> {
     })
 }
 
+/// Text starting with `odds`, such as `/roll odds 1d20+7`, gets the odds
+/// of each value, and with `stats`, such as `/roll stats 3d6+2`, gets a
+/// summary of them. With `hist`, such as `/roll hist 3d6`, it's rolled
+/// alongside a chart of the odds, and with `compare`, such as
+/// `/roll compare 2d6 vs 1d12`, two rolls are charted against each other.
+/// Anything else is just rolled.
+fn respond(username: &str, text: &str, response: &mut Vec<u8>) {
+    let text = text.trim_start();
+    let result = if text.len() > MAX_INPUT_LENGTH {
+        write!(
+            response,
+            "{} rolled something too long, at most {} characters can be rolled",
+            username, MAX_INPUT_LENGTH
+        )
+    } else if let Some(input) = text.strip_prefix("odds ") {
+        dice_roll::odds_with_limits(username, input, response, limits())
+    } else if let Some(input) = text.strip_prefix("hist ") {
+        dice_roll::histogram_with_limits(username, input, response, limits(), Markup::Slack)
+    } else if let Some(input) = text.strip_prefix("stats ") {
+        dice_roll::stats_with_limits(username, input, response, limits())
+    } else if let Some((left, right)) = text
+        .strip_prefix("compare ")
+        .and_then(|input| input.split_once(" vs "))
+    {
+        dice_roll::compare_with_limits(username, left, right, response, limits(), Markup::Slack)
+    } else {
+        dice_roll::main_with_limits(username, text, response, limits())
+    };
+
+    result.unwrap()
+}
+
 fn config() -> Config {
     Config::build(Environment::Staging)
         .address(get_address())
@@ -56,6 +94,20 @@ fn config() -> Config {
         .unwrap()
 }
 
+/// Rolls come from anyone in the channel, so they get less to work with than
+/// those from the command line.
+fn limits() -> Limits {
+    Limits {
+        max_dice: 1_000,
+        max_sides: 1_000_000,
+        max_explosions: 20,
+        max_rerolls: 20,
+        max_depth: 100,
+        max_combinations: 100_000,
+        max_simulated_dice: 1_000_000,
+    }
+}
+
 fn get_address() -> String {
     env::var("IP").unwrap_or_else(|_| DEFAULT_IP.to_string())
 }
@@ -0,0 +1,807 @@
+use crate::ast::{Expr, ExprKind, ExprOp, RollModifier, Span, UnaryOp};
+use crate::eval::function;
+use crate::eval::maths;
+use crate::eval::modifier;
+use crate::eval::{
+    Chance, DiceSource, Die, ErrorKind, Eval, Limit, Limits, Roll, Sides, Simulation,
+};
+
+use ::rand::rngs::mock::StepRng;
+use ::std::collections::BTreeMap;
+
+mod bounds;
+mod error;
+mod ratio;
+mod stats;
+mod versus;
+
+pub(crate) use self::bounds::bounds;
+pub use self::error::Error;
+pub use self::ratio::Ratio;
+pub use self::stats::Stats;
+pub use self::versus::Versus;
+
+/// How many times an expression is rolled, to estimate its odds.
+const NUM_SAMPLES: usize = 10_000;
+
+/// The fewest rolls the odds can be estimated from, before they are too rough
+/// to be worth giving.
+const MIN_SAMPLES: usize = 1_000;
+
+/// The odds of an expression, either worked out exactly,
+/// or estimated by rolling it many times.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Odds {
+    Exact(Distribution),
+    Estimated(Simulation),
+}
+
+impl Odds {
+    /// Each value, from lowest to highest, and how likely it is.
+    pub fn probabilities(&self) -> Vec<(i64, f64)> {
+        match self {
+            Self::Exact(distribution) => distribution
+                .probabilities()
+                .iter()
+                .map(|(&value, probability)| (value, probability.to_f64()))
+                .collect(),
+            Self::Estimated(simulation) => simulation
+                .estimates()
+                .into_iter()
+                .map(|(value, estimate)| (value, estimate.probability))
+                .collect(),
+        }
+    }
+
+    /// The chance of coming out as anything other than zero,
+    /// which is whether a comparison, such as `1d20 >= 15`, is true.
+    pub fn chance(&self) -> Chance {
+        match self {
+            // Summing nothing gives `-0.0`, which would be written as `-0.00%`.
+            Self::Exact(distribution) => Chance::Exact(
+                distribution
+                    .probabilities()
+                    .iter()
+                    .filter(|&(&value, _)| value != 0)
+                    .fold(0.0, |total, (_, probability)| total + probability.to_f64()),
+            ),
+            Self::Estimated(simulation) => {
+                Chance::Estimated(simulation.chance(), simulation.num_samples())
+            }
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        match self {
+            Self::Exact(distribution) => distribution.stats(),
+            Self::Estimated(simulation) => simulation.stats(),
+        }
+    }
+}
+
+/// Every value an expression can come out as, along with how likely it is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Distribution {
+    probabilities: BTreeMap<i64, Ratio>,
+}
+
+impl Distribution {
+    pub fn constant(value: i64) -> Self {
+        let mut probabilities = BTreeMap::new();
+        probabilities.insert(value, Ratio::one());
+
+        Self { probabilities }
+    }
+
+    /// Each value, from lowest to highest, and how likely it is.
+    pub fn probabilities(&self) -> &BTreeMap<i64, Ratio> {
+        &self.probabilities
+    }
+
+    pub fn probability(&self, value: i64) -> Ratio {
+        self.probabilities
+            .get(&value)
+            .copied()
+            .unwrap_or_else(Ratio::zero)
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats::new(self)
+    }
+
+    /// A die where each face is as likely as the others.
+    fn uniform(faces: &[i64]) -> Self {
+        let mut counts = BTreeMap::new();
+        for &face in faces {
+            *counts.entry(face).or_insert(0) += 1;
+        }
+
+        Self::from_counts(counts, faces.len() as u128)
+    }
+
+    fn from_counts(counts: BTreeMap<i64, u128>, total: u128) -> Self {
+        let probabilities = counts
+            .into_iter()
+            .map(|(value, count)| (value, Ratio::new(count, total)))
+            .collect();
+
+        Self { probabilities }
+    }
+
+    fn add_probability(&mut self, value: i64, probability: Ratio) {
+        let total = self.probability(value) + probability;
+        self.probabilities.insert(value, total);
+    }
+}
+
+/// Works out the exact odds of each value the expression can come out as.
+pub fn calculate(ast: &Expr, limits: &Limits) -> Result<Distribution, Error> {
+    Calculator {
+        limits,
+        depth: 0,
+        num_combinations: 0,
+    }
+    .visit(ast)
+}
+
+/// Works out the exact odds of the expression where it can, and otherwise
+/// estimates them by rolling it many times.
+///
+/// The only errors left are those which rolling would also run into.
+pub fn odds(ast: &Expr, limits: &Limits) -> Result<Odds, crate::eval::Error> {
+    odds_with_eval(ast, &mut Eval::new_with_limits(limits.clone()))
+}
+
+/// Works out the odds as `odds` does, but estimates them by rolling with
+/// the eval, so with its limits and where its dice come from.
+pub fn odds_with_eval<S: DiceSource>(
+    ast: &Expr,
+    eval: &mut Eval<S>,
+) -> Result<Odds, crate::eval::Error> {
+    match calculate(ast, eval.limits()) {
+        Ok(distribution) => Ok(Odds::Exact(distribution)),
+        Err(Error::Eval(err)) if !is_too_complex(&err) => Err(err),
+        Err(_) => estimate(ast, eval).map(Odds::Estimated),
+    }
+}
+
+/// Rolls the expression many times, unless it uses up the dice before
+/// enough rolls have been made to say anything about it.
+fn estimate<S: DiceSource>(
+    ast: &Expr,
+    eval: &mut Eval<S>,
+) -> Result<Simulation, crate::eval::Error> {
+    let simulation = eval.simulate(ast, NUM_SAMPLES)?;
+    if simulation.num_samples() < MIN_SAMPLES {
+        let limit = Limit::SimulatedDice(eval.limits().max_simulated_dice);
+        return Err(crate::eval::Error::from(ErrorKind::LimitExceeded(limit)).at(ast.span));
+    }
+
+    Ok(simulation)
+}
+
+/// Works out the odds of the expression, and then summarises them.
+pub fn stats(ast: &Expr, limits: &Limits) -> Result<Stats, crate::eval::Error> {
+    odds(ast, limits).map(|odds| odds.stats())
+}
+
+/// Works out the odds of both expressions, and how often each beats the other.
+pub fn versus(left: &Expr, right: &Expr, limits: &Limits) -> Result<Versus, crate::eval::Error> {
+    Ok(Versus::new(odds(left, limits)?, odds(right, limits)?))
+}
+
+/// Running out of combinations is the one limit which rolling doesn't have.
+fn is_too_complex(err: &crate::eval::Error) -> bool {
+    matches!(err.kind, ErrorKind::LimitExceeded(Limit::Combinations(_)))
+}
+
+struct Calculator<'a> {
+    limits: &'a Limits,
+
+    /// How deeply nested the expression being visited is.
+    depth: usize,
+
+    /// How many combinations of outcomes have been worked through so far,
+    /// across the whole expression.
+    num_combinations: usize,
+}
+
+impl<'a> Calculator<'a> {
+    /// Errors are blamed on the innermost expression they came from, as when rolling.
+    fn visit(&mut self, ast: &Expr) -> Result<Distribution, Error> {
+        self.depth += 1;
+        if self.depth > self.limits.max_depth {
+            let err = self.limit_exceeded(Limit::Depth(self.limits.max_depth));
+            return Err(err.at(ast.span));
+        }
+
+        let distribution = match &ast.kind {
+            ExprKind::Integer(n) => Ok(Distribution::constant(*n)),
+            ExprKind::Operator(ExprOp::Roll(modifiers), left_expr, right_expr) => {
+                self.visit_roll(left_expr, right_expr, modifiers)
+            }
+            ExprKind::Operator(op, left_expr, right_expr) => {
+                self.visit_op(op, left_expr, right_expr)
+            }
+            ExprKind::Unary(op, expr) => self.visit_unary(op, expr),
+            ExprKind::Call(name, args) => self.visit_call(name, args),
+
+            // The odds of a chance are the odds of its condition, and as when
+            // rolling, it has to be on its own.
+            ExprKind::Chance(_) if self.depth > 1 => Err(ErrorKind::NestedChance.into()),
+            ExprKind::Chance(condition) => self.visit(condition),
+
+            // A die on its own is rolled once, like `d6`.
+            ExprKind::Fudge | ExprKind::Faces(_) => {
+                let span = Span::new(ast.span.start, ast.span.start);
+                self.visit_roll(&Expr::new(ExprKind::Integer(1), span), ast, &[])
+            }
+        };
+
+        self.depth -= 1;
+        distribution.map_err(|err| err.at(ast.span))
+    }
+
+    fn visit_op(
+        &mut self,
+        op: &ExprOp,
+        left_expr: &Expr,
+        right_expr: &Expr,
+    ) -> Result<Distribution, Error> {
+        let left = self.visit(left_expr)?;
+        let right = self.visit(right_expr)?;
+
+        self.combine(&left, &right, |l, r| maths::apply(op, l, r))
+    }
+
+    fn visit_unary(&mut self, op: &UnaryOp, expr: &Expr) -> Result<Distribution, Error> {
+        let distribution = self.visit(expr)?;
+
+        self.combine(&distribution, &Distribution::constant(0), |n, _| {
+            maths::apply_unary(op, n)
+        })
+    }
+
+    fn visit_call(&mut self, name: &str, args: &[Expr]) -> Result<Distribution, Error> {
+        let function = function::find(name, args.len())?;
+        let distributions = args
+            .iter()
+            .map(|arg| self.visit(arg))
+            .collect::<Result<Vec<Distribution>, Error>>()?;
+
+        self.combine_all(&distributions, |values| function.apply(values))
+    }
+
+    /// The number of dice and the sides can be rolls themselves, i.e. `(1d4)d6`.
+    /// The odds of each pairing are worked out, and then mixed together.
+    fn visit_roll(
+        &mut self,
+        left_expr: &Expr,
+        right_expr: &Expr,
+        modifiers: &[RollModifier],
+    ) -> Result<Distribution, Error> {
+        let num_dice = self.visit(left_expr)?;
+        let all_sides = self.visit_sides(right_expr)?;
+
+        let mut distribution = Distribution {
+            probabilities: BTreeMap::new(),
+        };
+        for (sides, sides_probability) in &all_sides {
+            self.roll(
+                &mut distribution,
+                &num_dice,
+                sides,
+                *sides_probability,
+                modifiers,
+            )?;
+        }
+
+        Ok(distribution)
+    }
+
+    fn visit_sides(&mut self, ast: &Expr) -> Result<Vec<(Sides, Ratio)>, Error> {
+        let all_sides = match &ast.kind {
+            ExprKind::Fudge => vec![(Sides::Fudge, Ratio::one())],
+            ExprKind::Faces(faces) => vec![(Sides::Faces(faces.clone()), Ratio::one())],
+            _ => self
+                .visit(ast)?
+                .probabilities()
+                .iter()
+                .map(|(&n, &probability)| (Sides::Number(n), probability))
+                .collect(),
+        };
+
+        for (sides, _) in &all_sides {
+            if maths::num_faces(sides) > self.limits.max_sides {
+                let err = self.limit_exceeded(Limit::Sides(self.limits.max_sides));
+                return Err(err.at(ast.span));
+            }
+        }
+
+        Ok(all_sides)
+    }
+
+    /// Mixes in the odds of rolling each number of dice with the given sides.
+    /// Sums of more dice are built up from sums of fewer, so `3d6` is the
+    /// odds of `2d6` combined with one more `d6`, rather than starting over.
+    fn roll(
+        &mut self,
+        distribution: &mut Distribution,
+        num_dice: &Distribution,
+        sides: &Sides,
+        sides_probability: Ratio,
+        modifiers: &[RollModifier],
+    ) -> Result<(), Error> {
+        if maths::num_faces(sides) as usize > self.limits.max_combinations {
+            return Err(self.limit_exceeded(Limit::Combinations(self.limits.max_combinations)));
+        }
+
+        let faces = maths::faces(sides);
+        let die = Distribution::uniform(&faces);
+        let mut sum = Distribution::constant(0);
+        let mut num_summed = 0;
+
+        for (&n, &num_dice_probability) in num_dice.probabilities() {
+            if n > self.limits.max_dice {
+                return Err(self.limit_exceeded(Limit::Dice(self.limits.max_dice)));
+            }
+
+            let enumerated;
+            let roll = if modifiers.is_empty() {
+                for _ in num_summed..n {
+                    sum = self.combine(&sum, &die, maths::add)?;
+                }
+                num_summed = num_summed.max(n);
+                &sum
+            } else {
+                enumerated = self.enumerate_dice(n, sides, &faces, modifiers)?;
+                &enumerated
+            };
+
+            self.count_combinations(roll.probabilities.len())?;
+            let probability = num_dice_probability * sides_probability;
+            for (&value, &roll_probability) in roll.probabilities() {
+                distribution.add_probability(value, roll_probability * probability);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Modifiers such as keeping the highest dice depend on every die at once,
+    /// so every possible roll is tried in turn.
+    fn enumerate_dice(
+        &mut self,
+        num_dice: i64,
+        sides: &Sides,
+        faces: &[i64],
+        modifiers: &[RollModifier],
+    ) -> Result<Distribution, Error> {
+        if let Some(modifier) = modifiers.iter().find(|modifier| !is_exact(modifier)) {
+            return Err(Error::NotExact(modifier.clone()));
+        }
+
+        let num_dice = num_dice.max(0) as usize;
+        let num_rolls = u32::try_from(num_dice)
+            .ok()
+            .and_then(|num_dice| faces.len().checked_pow(num_dice))
+            .unwrap_or(usize::MAX);
+        self.count_combinations(num_rolls)?;
+
+        // Exact modifiers never roll more dice, and so never use this.
+        let mut rng = StepRng::new(0, 0);
+        let mut counts = BTreeMap::new();
+        let mut indexes = vec![0; num_dice];
+
+        for _ in 0..num_rolls {
+            let mut dice = indexes
+                .iter()
+                .map(|&i| Die::new(faces[i]))
+                .collect::<Vec<Die>>();
+            for modifier in modifiers {
+                modifier::apply(&mut rng, self.limits, &mut dice, sides, modifier)?;
+            }
+
+            let roll = Roll {
+                num_dice: num_dice as i64,
+                sides: sides.clone(),
+                modifiers: modifiers.to_vec(),
+                dice,
+            };
+            *counts.entry(roll.value()?.to_i64()?).or_insert(0) += 1;
+
+            next_roll(&mut indexes, faces.len());
+        }
+
+        Ok(Distribution::from_counts(counts, num_rolls as u128))
+    }
+
+    /// Works out every pairing of the left and right values.
+    fn combine(
+        &mut self,
+        left: &Distribution,
+        right: &Distribution,
+        op: impl Fn(i64, i64) -> Result<i64, ErrorKind>,
+    ) -> Result<Distribution, Error> {
+        let num_combinations = left
+            .probabilities
+            .len()
+            .saturating_mul(right.probabilities.len());
+        self.count_combinations(num_combinations)?;
+
+        let mut distribution = Distribution {
+            probabilities: BTreeMap::new(),
+        };
+        for (&l, &left_probability) in left.probabilities() {
+            for (&r, &right_probability) in right.probabilities() {
+                distribution.add_probability(op(l, r)?, left_probability * right_probability);
+            }
+        }
+
+        Ok(distribution)
+    }
+
+    /// Works out every combination of values, one from each distribution,
+    /// such as for the arguments of a function.
+    fn combine_all(
+        &mut self,
+        distributions: &[Distribution],
+        op: impl Fn(&[i64]) -> Result<i64, ErrorKind>,
+    ) -> Result<Distribution, Error> {
+        let num_combinations = distributions.iter().fold(1, |num_combinations: usize, d| {
+            num_combinations.saturating_mul(d.probabilities.len())
+        });
+        self.count_combinations(num_combinations)?;
+
+        let outcomes = distributions
+            .iter()
+            .map(|d| d.probabilities().iter().collect())
+            .collect::<Vec<Vec<(&i64, &Ratio)>>>();
+        let mut distribution = Distribution {
+            probabilities: BTreeMap::new(),
+        };
+        let mut indexes = vec![0; outcomes.len()];
+        let mut values = vec![0; outcomes.len()];
+
+        for _ in 0..num_combinations {
+            let mut probability = Ratio::one();
+            for ((value, outcome), &i) in values.iter_mut().zip(&outcomes).zip(&indexes) {
+                let (&v, &p) = outcome[i];
+                *value = v;
+                probability = probability * p;
+            }
+            distribution.add_probability(op(&values)?, probability);
+
+            next_combination(&mut indexes, &outcomes);
+        }
+
+        Ok(distribution)
+    }
+
+    /// Counts combinations against the limit, which is for the whole
+    /// expression rather than each step of working it out.
+    fn count_combinations(&mut self, num_combinations: usize) -> Result<(), Error> {
+        self.num_combinations = self.num_combinations.saturating_add(num_combinations);
+        if self.num_combinations > self.limits.max_combinations {
+            return Err(self.limit_exceeded(Limit::Combinations(self.limits.max_combinations)));
+        }
+
+        Ok(())
+    }
+
+    fn limit_exceeded(&self, limit: Limit) -> Error {
+        Error::Eval(ErrorKind::LimitExceeded(limit).into())
+    }
+}
+
+/// Modifiers which only look at the dice rolled, and never roll more.
+fn is_exact(modifier: &RollModifier) -> bool {
+    matches!(
+        modifier,
+        RollModifier::KeepHighest(_)
+            | RollModifier::KeepLowest(_)
+            | RollModifier::DropHighest(_)
+            | RollModifier::DropLowest(_)
+            | RollModifier::Success(_)
+            | RollModifier::Failure(_)
+    )
+}
+
+/// Moves on to the next roll, counting up through the faces like an odometer.
+fn next_roll(indexes: &mut [usize], num_faces: usize) {
+    for index in indexes.iter_mut() {
+        *index += 1;
+        if *index < num_faces {
+            return;
+        }
+
+        *index = 0;
+    }
+}
+
+/// Moves on to the next combination, like `next_roll`, but where each
+/// index counts through outcomes of its own.
+fn next_combination<T>(indexes: &mut [usize], outcomes: &[Vec<T>]) {
+    for (index, outcome) in indexes.iter_mut().zip(outcomes) {
+        *index += 1;
+        if *index < outcome.len() {
+            return;
+        }
+
+        *index = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn it_should_give_numbers_a_probability_of_one() {
+        let distribution = test_calculate("2 + 3").unwrap();
+
+        assert_eq!(distribution.probabilities().len(), 1);
+        assert_eq!(distribution.probability(5), Ratio::one());
+    }
+
+    #[test]
+    fn it_should_sum_dice() {
+        let distribution = test_calculate("2d6").unwrap();
+
+        assert_eq!(distribution.probabilities().len(), 11);
+        assert_eq!(distribution.probability(2), Ratio::new(1, 36));
+        assert_eq!(distribution.probability(7), Ratio::new(1, 6));
+        assert_eq!(distribution.probability(13), Ratio::zero());
+    }
+
+    #[test]
+    fn it_should_work_out_the_odds_of_beating_a_target() {
+        let distribution = test_calculate("1d20 + 7").unwrap();
+        let beats_fifteen = distribution
+            .probabilities()
+            .range(15..)
+            .fold(Ratio::zero(), |total, (_, &p)| total + p);
+
+        assert_eq!(beats_fifteen, Ratio::new(13, 20));
+    }
+
+    #[test]
+    fn it_should_approximate_odds_too_fine_to_keep_exactly() {
+        let distribution = test_calculate("100d6").unwrap();
+
+        assert!((distribution.probability(350).to_f64() - 0.023322606).abs() < 1e-9);
+        assert!((total(&distribution).to_f64() - 1.0).abs() < 1e-9);
+        assert_eq!(distribution.stats().median, 350);
+    }
+
+    #[test]
+    fn it_should_count_combinations_across_the_whole_expression() {
+        let limits = Limits {
+            max_combinations: 100,
+            ..Limits::default()
+        };
+
+        assert!(calculate(&parse("2d6").unwrap()[0], &limits).is_ok());
+        assert_eq!(
+            calculate(&parse("4d6").unwrap()[0], &limits),
+            Err(Error::Eval(
+                crate::eval::Error::from(ErrorKind::LimitExceeded(Limit::Combinations(100)))
+                    .at(Span::new(0, 3))
+            ))
+        );
+    }
+
+    #[test]
+    fn it_should_build_sums_of_more_dice_from_sums_of_fewer() {
+        let distribution = test_calculate("(1d10000)d1 > 5").unwrap();
+
+        assert_eq!(distribution.probability(1), Ratio::new(9_995, 10_000));
+    }
+
+    #[test]
+    fn it_should_mix_the_odds_of_nested_dice() {
+        let distribution = test_calculate("(1d4)d6").unwrap();
+
+        assert_eq!(distribution.probability(1), Ratio::new(1, 24));
+        assert_eq!(
+            distribution.probability(24),
+            Ratio::new(1, 4 * 6 * 6 * 6 * 6)
+        );
+        assert_eq!(total(&distribution), Ratio::one());
+    }
+
+    #[test]
+    fn it_should_work_out_the_odds_of_keeping_dice() {
+        let distribution = test_calculate("4d6kh3").unwrap();
+
+        assert_eq!(distribution.probability(18), Ratio::new(21, 1296));
+        assert_eq!(distribution.probability(3), Ratio::new(1, 1296));
+        assert_eq!(total(&distribution), Ratio::one());
+    }
+
+    #[test]
+    fn it_should_round_halved_dice_either_way() {
+        let rounded_down = test_calculate("1d6 // 2").unwrap();
+        let rounded_up = test_calculate("1d6 /^ 2").unwrap();
+
+        assert_eq!(rounded_down.probability(0), Ratio::new(1, 6));
+        assert_eq!(rounded_up.probability(0), Ratio::zero());
+        assert_eq!(rounded_up.probability(3), Ratio::new(1, 3));
+    }
+
+    #[test]
+    fn it_should_work_out_the_odds_of_functions() {
+        let distribution = test_calculate("max(1d6, 1d6)").unwrap();
+
+        assert_eq!(distribution.probability(1), Ratio::new(1, 36));
+        assert_eq!(distribution.probability(6), Ratio::new(11, 36));
+
+        let distribution = test_calculate("clamp(2d6, 4, 1d2 + 9)").unwrap();
+
+        assert_eq!(distribution.probability(4), Ratio::new(6, 36));
+        assert_eq!(distribution.probability(11), Ratio::new(1, 24));
+        assert_eq!(distribution.probability(12), Ratio::zero());
+    }
+
+    #[test]
+    fn it_should_negate_every_outcome() {
+        let distribution = test_calculate("-1d6").unwrap();
+
+        assert_eq!(distribution.probability(-6), Ratio::new(1, 6));
+        assert_eq!(distribution.probability(6), Ratio::zero());
+    }
+
+    #[test]
+    fn it_should_work_out_the_odds_of_fudge_and_custom_dice() {
+        assert_eq!(
+            test_calculate("4dF").unwrap().probability(4),
+            Ratio::new(1, 81)
+        );
+        assert_eq!(
+            test_calculate("d{1,1,2}").unwrap().probability(1),
+            Ratio::new(2, 3)
+        );
+    }
+
+    #[test]
+    fn it_should_not_work_out_exploding_dice() {
+        assert_eq!(
+            test_calculate("1d6!"),
+            Err(Error::NotExact(RollModifier::Explode(None)))
+        );
+    }
+
+    #[test]
+    fn it_should_error_when_any_outcome_divides_by_zero() {
+        let divide_by_zero = crate::eval::Error::from(ErrorKind::DivideByZero);
+
+        assert_eq!(
+            test_calculate("1 / (1d2 - 1)"),
+            Err(Error::Eval(divide_by_zero.at(Span::new(0, 13))))
+        );
+    }
+
+    #[test]
+    fn it_should_give_comparisons_a_chance_of_being_true() {
+        let distribution = test_calculate("1d20 + 5 >= 15").unwrap();
+
+        assert_eq!(distribution.probability(1), Ratio::new(11, 20));
+        assert_eq!(distribution.probability(0), Ratio::new(9, 20));
+    }
+
+    #[test]
+    fn it_should_error_when_a_chance_is_nested() {
+        let chance = Expr::chance(parse("1d6 > 3").unwrap().remove(0));
+        let ast = Expr::operator(ExprOp::Add, Expr::integer(1), chance);
+        let nested_chance = crate::eval::Error::from(ErrorKind::NestedChance);
+
+        assert_eq!(
+            calculate(&ast, &Limits::default()),
+            Err(Error::Eval(nested_chance.at(Span::default())))
+        );
+    }
+
+    #[test]
+    fn it_should_summarise_the_odds() {
+        let stats = test_calculate("3d6 + 2").unwrap().stats();
+
+        assert_eq!(stats.min, 5);
+        assert_eq!(stats.max, 20);
+        assert!((stats.mean - 12.5).abs() < 1e-9);
+        assert_eq!(stats.median, 12);
+        assert_eq!(stats.mode, 12);
+        assert!((stats.variance() - 8.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_find_the_median_on_an_even_split() {
+        assert_eq!(test_calculate("1d10").unwrap().stats().median, 5);
+        assert_eq!(test_calculate("1d2").unwrap().stats().median, 1);
+    }
+
+    #[test]
+    fn it_should_estimate_the_odds_when_they_cant_be_worked_out() {
+        let limits = Limits::default();
+        let exact = odds(&parse("2d6").unwrap()[0], &limits).unwrap();
+        let estimated = odds(&parse("2d6!").unwrap()[0], &limits).unwrap();
+
+        assert!(matches!(exact, Odds::Exact(_)));
+        assert!(matches!(estimated, Odds::Estimated(_)));
+        assert_eq!(
+            odds(&parse("1 / 0").unwrap()[0], &limits).map_err(|err| err.kind),
+            Err(ErrorKind::DivideByZero)
+        );
+    }
+
+    #[test]
+    fn it_should_error_when_too_few_rolls_can_be_made_to_estimate_the_odds() {
+        let limits = Limits {
+            max_simulated_dice: 50_000,
+            ..Limits::default()
+        };
+        let ast = &parse("100d6!").unwrap()[0];
+
+        assert_eq!(
+            odds(ast, &limits).map_err(|err| err.kind),
+            Err(ErrorKind::LimitExceeded(Limit::SimulatedDice(50_000)))
+        );
+    }
+
+    #[test]
+    fn it_should_take_the_bounds_of_estimated_stats_from_the_faces() {
+        let stats = stats(&parse("10d10>=7").unwrap()[0], &Limits::default()).unwrap();
+
+        assert_eq!((stats.min, stats.max), (0, 10));
+        assert_eq!(stats.num_samples, Some(10_000));
+        assert_eq!(test_calculate("3d6").unwrap().stats().num_samples, None);
+    }
+
+    #[test]
+    fn it_should_bound_values_by_the_faces_and_modifiers() {
+        let tests = [
+            ("4d6kh3", Some((3, 18))),
+            ("1d20r<3 + 1", Some((5, 21))),
+            ("1d6 - 1d4", Some((-3, 5))),
+            ("(1d6)d(1d4)", Some((1, 24))),
+            ("3d6!", Some((3, 1_815))),
+            ("2d6!p>=5", Some((2, 1_008))),
+            ("4dF>=1f<0", Some((-4, 4))),
+            ("2d{1,3,5}!>=5", Some((2, 1_006))),
+            ("max(1d6, 1d8) // 2", Some((0, 4))),
+            ("1d6 % 4 + (1d6 > 3)", Some((0, 4))),
+            ("(1d2)d(1d6)!", None),
+            ("4d6!r", None),
+            ("1d6r<5!>=5", Some((7, 604))),
+        ];
+
+        for (expr, expected) in tests {
+            let ast = &parse(expr).unwrap()[0];
+            assert_eq!(bounds(ast, &Limits::default()), expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn it_should_work_out_how_often_one_roll_beats_another() {
+        let limits = Limits::default();
+        let left = parse("2d6").unwrap().remove(0);
+        let right = parse("1d12").unwrap().remove(0);
+        let versus = versus(&left, &right, &limits).unwrap();
+
+        assert!((versus.left_wins - 1.0 / 2.0).abs() < 1e-9);
+        assert!((versus.right_wins - 5.0 / 12.0).abs() < 1e-9);
+        assert!((versus.draw - 1.0 / 12.0).abs() < 1e-9);
+    }
+
+    fn test_calculate(expr: &str) -> Result<Distribution, Error> {
+        let ast = parse(expr).unwrap().remove(0);
+        calculate(&ast, &Limits::default())
+    }
+
+    fn total(distribution: &Distribution) -> Ratio {
+        distribution
+            .probabilities()
+            .values()
+            .fold(Ratio::zero(), |total, &p| total + p)
+    }
+}
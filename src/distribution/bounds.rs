@@ -0,0 +1,430 @@
+use crate::ast::{Comparison, Condition, Expr, ExprKind, ExprOp, RollModifier, UnaryOp};
+use crate::eval::function;
+use crate::eval::maths;
+use crate::eval::modifier;
+use crate::eval::{Limits, Sides};
+
+/// The lowest and highest values the expression could come out as, worked out
+/// from the faces of its dice and what's done with them rather than from rolling.
+///
+/// Returns `None` when there's no simple way to tell, such as for dice which
+/// are rerolled after they explode.
+pub(crate) fn bounds(ast: &Expr, limits: &Limits) -> Option<(i64, i64)> {
+    match &ast.kind {
+        ExprKind::Integer(n) => Some((*n, *n)),
+        ExprKind::Operator(ExprOp::Roll(modifiers), left_expr, right_expr) => {
+            roll_bounds(bounds(left_expr, limits)?, right_expr, modifiers, limits)
+        }
+        ExprKind::Operator(op, left_expr, right_expr) => {
+            op_bounds(op, bounds(left_expr, limits)?, bounds(right_expr, limits)?)
+        }
+        ExprKind::Unary(UnaryOp::Neg, expr) => {
+            let (min, max) = bounds(expr, limits)?;
+            Some(clamp(-(max as i128), -(min as i128)))
+        }
+        ExprKind::Unary(UnaryOp::Pos, expr) => bounds(expr, limits),
+        ExprKind::Call(name, args) => {
+            let args = args
+                .iter()
+                .map(|arg| bounds(arg, limits))
+                .collect::<Option<Vec<(i64, i64)>>>()?;
+            call_bounds(name, &args)
+        }
+        ExprKind::Chance(condition) => bounds(condition, limits),
+        ExprKind::Fudge | ExprKind::Faces(_) => roll_bounds((1, 1), ast, &[], limits),
+    }
+}
+
+fn op_bounds(op: &ExprOp, left: (i64, i64), right: (i64, i64)) -> Option<(i64, i64)> {
+    match op {
+        ExprOp::Add | ExprOp::Sub | ExprOp::Mult => corners(left, right, |l, r| match op {
+            ExprOp::Add => l + r,
+            ExprOp::Sub => l - r,
+            _ => l * r,
+        }),
+        ExprOp::Div | ExprOp::FloorDiv | ExprOp::CeilDiv | ExprOp::RoundDiv => {
+            divide_bounds(left, right, |l, r| maths::apply(op, l, r).ok())
+        }
+        ExprOp::Mod => mod_bounds(left, right),
+        ExprOp::Pow => pow_bounds(left, right),
+        ExprOp::Compare(comparison) => Some(compare_bounds(comparison, left, right)),
+        ExprOp::Roll(_) => unreachable!("rolls have bounds of their own"),
+    }
+}
+
+/// The most and least of an operation which only ever grows or shrinks as
+/// each side does, and so is at its most and least at the corners.
+fn corners(
+    left: (i64, i64),
+    right: (i64, i64),
+    op: impl Fn(i128, i128) -> i128,
+) -> Option<(i64, i64)> {
+    let values = [
+        op(left.0 as i128, right.0 as i128),
+        op(left.0 as i128, right.1 as i128),
+        op(left.1 as i128, right.0 as i128),
+        op(left.1 as i128, right.1 as i128),
+    ];
+
+    Some(clamp(*values.iter().min()?, *values.iter().max()?))
+}
+
+/// Dividing only grows or shrinks as each side does while the divisor stays
+/// on one side of zero, so each side of zero is worked out on its own.
+fn divide_bounds(
+    left: (i64, i64),
+    right: (i64, i64),
+    op: impl Fn(i64, i64) -> Option<i64>,
+) -> Option<(i64, i64)> {
+    let negative = (right.0, right.1.min(-1));
+    let positive = (right.0.max(1), right.1);
+
+    let mut values = Vec::new();
+    for divisor in [negative, positive] {
+        if divisor.0 <= divisor.1 {
+            for l in [left.0, left.1] {
+                values.push(op(l, divisor.0)?);
+                values.push(op(l, divisor.1)?);
+            }
+        }
+    }
+
+    Some((*values.iter().min()?, *values.iter().max()?))
+}
+
+/// What's left over has the sign of the divisor, and is smaller than it.
+fn mod_bounds(left: (i64, i64), right: (i64, i64)) -> Option<(i64, i64)> {
+    let mut min = None;
+    let mut max = None;
+
+    if right.1 > 0 {
+        let highest = if left.0 >= 0 {
+            left.1.min(right.1 - 1)
+        } else {
+            right.1 - 1
+        };
+        min = Some(0);
+        max = Some(highest);
+    }
+    if right.0 < 0 {
+        let lowest = if left.1 <= 0 {
+            left.0.max(right.0 + 1)
+        } else {
+            right.0 + 1
+        };
+        min = Some(lowest);
+        max = max.or(Some(0));
+    }
+
+    Some((min?, max?))
+}
+
+/// Only worked out for bases which can't be negative, as those only grow as
+/// either side does, other than `0^0` being `1`.
+fn pow_bounds(left: (i64, i64), right: (i64, i64)) -> Option<(i64, i64)> {
+    if left.0 < 0 || right.1 < 0 {
+        return None;
+    }
+
+    let right = (right.0.max(0), right.1);
+    corners(left, right, |l, r| {
+        let exponent = u32::try_from(r).unwrap_or(u32::MAX);
+        l.checked_pow(exponent).unwrap_or(i128::MAX)
+    })
+}
+
+fn compare_bounds(comparison: &Comparison, left: (i64, i64), right: (i64, i64)) -> (i64, i64) {
+    let is_apart = left.1 < right.0 || right.1 < left.0;
+    let is_same = left.0 == left.1 && right.0 == right.1 && left.0 == right.0;
+
+    let (is_always, is_never) = match comparison {
+        Comparison::Equal => (is_same, is_apart),
+        Comparison::NotEqual => (is_apart, is_same),
+        Comparison::Less => (left.1 < right.0, left.0 >= right.1),
+        Comparison::LessOrEqual => (left.1 <= right.0, left.0 > right.1),
+        Comparison::Greater => (left.0 > right.1, left.1 <= right.0),
+        Comparison::GreaterOrEqual => (left.0 >= right.1, left.1 < right.0),
+    };
+
+    (is_always as i64, !is_never as i64)
+}
+
+fn call_bounds(name: &str, args: &[(i64, i64)]) -> Option<(i64, i64)> {
+    match (name, args) {
+        ("abs", &[(min, max)]) if min >= 0 => Some((min, max)),
+        ("abs", &[(min, max)]) if max <= 0 => Some(clamp(-(max as i128), -(min as i128))),
+        ("abs", &[(min, max)]) => Some(clamp(0, (-(min as i128)).max(max as i128))),
+        ("floor", &[left, right]) => {
+            divide_bounds(left, right, |l, r| maths::floor_divide(l, r).ok())
+        }
+        ("ceil", &[left, right]) => {
+            divide_bounds(left, right, |l, r| maths::ceil_divide(l, r).ok())
+        }
+        ("round", &[left, right]) => {
+            divide_bounds(left, right, |l, r| maths::round_divide(l, r).ok())
+        }
+
+        // The others only grow as each argument does.
+        ("min" | "max" | "clamp", _) => {
+            let function = function::find(name, args.len()).ok()?;
+            let mins = args.iter().map(|&(min, _)| min).collect::<Vec<i64>>();
+            let maxes = args.iter().map(|&(_, max)| max).collect::<Vec<i64>>();
+
+            Some((function.apply(&mins).ok()?, function.apply(&maxes).ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// Works out the bounds of each die on its own, then how many dice there can be.
+///
+/// A die is tracked by a few of its possible faces, rather than all of them.
+/// These are the lowest and highest, along with those either side of where
+/// any condition of the roll starts or stops matching. Whichever faces a
+/// modifier picks out, the lowest and highest of those are always kept.
+fn roll_bounds(
+    num_dice: (i64, i64),
+    sides_expr: &Expr,
+    modifiers: &[RollModifier],
+    limits: &Limits,
+) -> Option<(i64, i64)> {
+    let (sides, number_sides) = match &sides_expr.kind {
+        ExprKind::Fudge => (Some(Sides::Fudge), None),
+        ExprKind::Faces(faces) => (Some(Sides::Faces(faces.clone())), None),
+        _ => {
+            let (min, max) = bounds(sides_expr, limits)?;
+            let sides = if min == max {
+                Some(Sides::Number(min))
+            } else {
+                None
+            };
+            (sides, Some((min, max)))
+        }
+    };
+    let conditions = conditions(modifiers, sides.as_ref())?;
+    let all_faces = match number_sides {
+        Some((min, max)) => representatives(min.min(1), max.max(-1), &conditions),
+        None => maths::faces(sides.as_ref()?),
+    };
+
+    let mut num_dice = (
+        num_dice.0.min(limits.max_dice).max(0),
+        num_dice.1.min(limits.max_dice).max(0),
+    );
+    let mut faces = all_faces.clone();
+    let mut chains = None;
+    let mut has_exploded = false;
+    let mut successes = Vec::new();
+    let mut failures = Vec::new();
+
+    for (modifier, condition) in modifiers.iter().zip(&conditions) {
+        // Dice which explode, or are counted as successes, are hard to keep
+        // track of once more dice are rolled or dropped afterwards.
+        let is_counting = matches!(
+            modifier,
+            RollModifier::Success(_) | RollModifier::Failure(_)
+        );
+        if has_exploded && !is_counting {
+            return None;
+        }
+        if !(successes.is_empty() && failures.is_empty()) && rolls_more(modifier) {
+            return None;
+        }
+
+        match modifier {
+            RollModifier::KeepHighest(n) | RollModifier::KeepLowest(n) => {
+                let n = if *n < 0 { i64::MAX } else { *n };
+                num_dice = (num_dice.0.min(n), num_dice.1.min(n));
+            }
+            RollModifier::DropHighest(n) | RollModifier::DropLowest(n) => {
+                let n = if *n < 0 { i64::MAX } else { *n };
+                num_dice = ((num_dice.0 - n).max(0), (num_dice.1 - n).max(0));
+            }
+            RollModifier::Explode(_) | RollModifier::Penetrate(_) => {
+                let penalty = matches!(modifier, RollModifier::Penetrate(_)) as i64;
+                let condition = condition.as_ref()?;
+                chains = Some(explode(&faces, &all_faces, condition, penalty, limits)?);
+                has_exploded = true;
+            }
+            RollModifier::Compound(_) => {
+                let condition = condition.as_ref()?;
+                let chains = explode(&faces, &all_faces, condition, 0, limits)?;
+                let (min, max) = chains_bounds(&chains, range)?;
+                faces = representatives(min, max, &conditions);
+                has_exploded = true;
+            }
+            RollModifier::Reroll(_) => {
+                faces = matching(&all_faces, condition.as_ref()?, false);
+                if faces.is_empty() {
+                    return None;
+                }
+            }
+            RollModifier::RerollOnce(_) => faces = all_faces.clone(),
+            RollModifier::Success(condition) => successes.push(condition),
+            RollModifier::Failure(condition) => failures.push(condition),
+        }
+    }
+
+    let chains = chains.unwrap_or_else(|| vec![vec![faces]]);
+    let per_die = if successes.is_empty() && failures.is_empty() {
+        chains_bounds(&chains, range)?
+    } else {
+        chains_bounds(&chains, |faces| {
+            let counts = faces.iter().map(|&face| {
+                let is_success = successes.iter().any(|c| modifier::is_match(c, face));
+                let is_failure = failures.iter().any(|c| modifier::is_match(c, face));
+                is_success as i64 - is_failure as i64
+            });
+            Some((counts.clone().min()?, counts.max()?))
+        })?
+    };
+
+    let (fewest, most) = (num_dice.0 as i128, num_dice.1 as i128);
+    let (lowest, highest) = (per_die.0 as i128, per_die.1 as i128);
+    Some(clamp(
+        (fewest * lowest).min(most * lowest),
+        (fewest * highest).max(most * highest),
+    ))
+}
+
+/// The chains a die could become by exploding `0`, `1` or the most times it
+/// can. Every die but the last matches the condition, and all but the first
+/// are rolled afresh with the penalty taken off. The totals of the chains
+/// in between lie between these.
+fn explode(
+    faces: &[i64],
+    all_faces: &[i64],
+    condition: &Condition,
+    penalty: i64,
+    limits: &Limits,
+) -> Option<Vec<Vec<Vec<i64>>>> {
+    let with_penalty = |faces: Vec<i64>| {
+        faces
+            .into_iter()
+            .map(|face| face - penalty)
+            .collect::<Vec<i64>>()
+    };
+    let first = matching(faces, condition, true);
+    let matches = with_penalty(matching(all_faces, condition, true));
+    let others = with_penalty(matching(all_faces, condition, false));
+    if others.is_empty() {
+        return None;
+    }
+
+    let mut chains = Vec::new();
+    let unexploded = matching(faces, condition, false);
+    if !unexploded.is_empty() {
+        chains.push(vec![unexploded]);
+    }
+    if !first.is_empty() && limits.max_explosions > 0 {
+        for num_explosions in [1, limits.max_explosions] {
+            let mut chain = vec![first.clone()];
+            for _ in 1..num_explosions {
+                chain.push(matches.clone());
+            }
+            chain.push(others.clone());
+            chains.push(chain);
+        }
+    }
+
+    Some(chains)
+}
+
+/// The lowest and highest totals of any chain, given the bounds of each die.
+fn chains_bounds(
+    chains: &[Vec<Vec<i64>>],
+    die_bounds: impl Fn(&[i64]) -> Option<(i64, i64)>,
+) -> Option<(i64, i64)> {
+    let mut totals = Vec::new();
+    for chain in chains {
+        let mut total = (0, 0);
+        for faces in chain {
+            let (min, max) = die_bounds(faces)?;
+            total = (total.0 + min as i128, total.1 + max as i128);
+        }
+        totals.push(total);
+    }
+
+    let min = totals.iter().map(|&(min, _)| min).min()?;
+    let max = totals.iter().map(|&(_, max)| max).max()?;
+    Some(clamp(min, max))
+}
+
+/// The faces between the lowest and highest which stand in for the rest.
+fn representatives(min: i64, max: i64, conditions: &[Option<Condition>]) -> Vec<i64> {
+    let mut faces = vec![min, max];
+    for condition in conditions.iter().flatten() {
+        let n = match *condition {
+            Condition::Equal(n) | Condition::AtLeast(n) | Condition::AtMost(n) => n,
+        };
+
+        // Either side of penetrating dice, which have one taken off.
+        for offset in -2..=2 {
+            if let Some(face) = n
+                .checked_add(offset)
+                .filter(|face| (min..=max).contains(face))
+            {
+                faces.push(face);
+            }
+        }
+    }
+
+    faces.sort_unstable();
+    faces.dedup();
+    faces
+}
+
+fn matching(faces: &[i64], condition: &Condition, is_match: bool) -> Vec<i64> {
+    faces
+        .iter()
+        .copied()
+        .filter(|&face| modifier::is_match(condition, face) == is_match)
+        .collect()
+}
+
+fn range(faces: &[i64]) -> Option<(i64, i64)> {
+    Some((*faces.iter().min()?, *faces.iter().max()?))
+}
+
+/// The condition of each modifier, with those left out filled in from the
+/// sides. Returns `None` when they're left out, but the sides aren't known.
+fn conditions(modifiers: &[RollModifier], sides: Option<&Sides>) -> Option<Vec<Option<Condition>>> {
+    modifiers
+        .iter()
+        .map(|modifier| match modifier {
+            RollModifier::Explode(condition)
+            | RollModifier::Compound(condition)
+            | RollModifier::Penetrate(condition) => match condition {
+                Some(condition) => Some(Some(condition.clone())),
+                None => Some(Some(modifier::condition_or_max(condition, sides?))),
+            },
+            RollModifier::Reroll(condition) | RollModifier::RerollOnce(condition) => {
+                match condition {
+                    Some(condition) => Some(Some(condition.clone())),
+                    None => Some(Some(modifier::condition_or_min(condition, sides?))),
+                }
+            }
+            RollModifier::Success(condition) | RollModifier::Failure(condition) => {
+                Some(Some(condition.clone()))
+            }
+            _ => Some(None),
+        })
+        .collect()
+}
+
+fn rolls_more(modifier: &RollModifier) -> bool {
+    matches!(
+        modifier,
+        RollModifier::Explode(_)
+            | RollModifier::Compound(_)
+            | RollModifier::Penetrate(_)
+            | RollModifier::Reroll(_)
+            | RollModifier::RerollOnce(_)
+    )
+}
+
+/// Values outside of these would have overflowed, and so never come up.
+fn clamp(min: i128, max: i128) -> (i64, i64) {
+    let clamp = |n: i128| n.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+    (clamp(min), clamp(max))
+}
@@ -0,0 +1,35 @@
+use crate::ast::{RollModifier, Span};
+use crate::eval;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// An error which would also happen when rolling, such as dividing by zero.
+    Eval(eval::Error),
+
+    /// Modifiers which roll more dice, like exploding or rerolling,
+    /// can carry on forever, and so have no exact odds.
+    NotExact(RollModifier),
+}
+
+impl Error {
+    /// Blames the expression at the span, for errors which would also happen
+    /// when rolling, unless an expression inside it was already blamed.
+    pub fn at(self, span: Span) -> Self {
+        match self {
+            Self::Eval(err) => Self::Eval(err.at(span)),
+            err => err,
+        }
+    }
+}
+
+impl From<eval::Error> for Error {
+    fn from(err: eval::Error) -> Self {
+        Self::Eval(err)
+    }
+}
+
+impl From<eval::ErrorKind> for Error {
+    fn from(kind: eval::ErrorKind) -> Self {
+        Self::Eval(kind.into())
+    }
+}
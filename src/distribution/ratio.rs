@@ -0,0 +1,118 @@
+use ::std::ops::{Add, Mul};
+
+/// A probability, kept exactly in its lowest terms for as long as it fits,
+/// and approximated once it becomes too fine to, as with the sum of `100d6`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ratio(Repr);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Repr {
+    Exact { numerator: u128, denominator: u128 },
+    Approximate(f64),
+}
+
+impl Ratio {
+    pub fn new(numerator: u128, denominator: u128) -> Self {
+        assert!(
+            denominator != 0,
+            "a ratio cannot have a denominator of zero"
+        );
+
+        let divisor = gcd(numerator, denominator);
+        Self(Repr::Exact {
+            numerator: numerator / divisor,
+            denominator: denominator / divisor,
+        })
+    }
+
+    pub fn zero() -> Self {
+        Self::new(0, 1)
+    }
+
+    pub fn one() -> Self {
+        Self::new(1, 1)
+    }
+
+    /// Whether this is a half or more, which is exact when the ratio is.
+    pub fn is_at_least_half(&self) -> bool {
+        match self.0 {
+            Repr::Exact {
+                numerator,
+                denominator,
+            } => numerator >= denominator - numerator,
+            Repr::Approximate(probability) => probability >= 0.5,
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        match self.0 {
+            Repr::Exact {
+                numerator,
+                denominator,
+            } => numerator as f64 / denominator as f64,
+            Repr::Approximate(probability) => probability,
+        }
+    }
+
+    /// Both ratios as exact fractions, if they are.
+    fn exact_pair(self, other: Self) -> Option<((u128, u128), (u128, u128))> {
+        match (self.0, other.0) {
+            (
+                Repr::Exact {
+                    numerator: a,
+                    denominator: b,
+                },
+                Repr::Exact {
+                    numerator: c,
+                    denominator: d,
+                },
+            ) => Some(((a, b), (c, d))),
+            _ => None,
+        }
+    }
+}
+
+impl Add for Ratio {
+    type Output = Self;
+
+    /// Falls back to an approximation when the exact sum is too fine to fit.
+    fn add(self, other: Self) -> Self {
+        let exact = self.exact_pair(other).and_then(|((a, b), (c, d))| {
+            let divisor = gcd(b, d);
+            let denominator = (b / divisor).checked_mul(d)?;
+            let left = a.checked_mul(d / divisor)?;
+            let right = c.checked_mul(b / divisor)?;
+
+            Some(Self::new(left.checked_add(right)?, denominator))
+        });
+
+        exact.unwrap_or_else(|| Self(Repr::Approximate(self.to_f64() + other.to_f64())))
+    }
+}
+
+impl Mul for Ratio {
+    type Output = Self;
+
+    /// Falls back to an approximation when the exact product is too fine to fit.
+    fn mul(self, other: Self) -> Self {
+        let exact = self.exact_pair(other).and_then(|((a, b), (c, d))| {
+            // Dividing out common factors first keeps the numbers small.
+            let left_divisor = gcd(a, d);
+            let right_divisor = gcd(c, b);
+            let numerator = (a / left_divisor).checked_mul(c / right_divisor)?;
+            let denominator = (b / right_divisor).checked_mul(d / left_divisor)?;
+
+            Some(Self::new(numerator, denominator))
+        });
+
+        exact.unwrap_or_else(|| Self(Repr::Approximate(self.to_f64() * other.to_f64())))
+    }
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
@@ -0,0 +1,122 @@
+use crate::distribution::{Distribution, Ratio};
+
+use ::std::collections::BTreeMap;
+
+/// A summary of a distribution, such as its average.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats {
+    pub min: i64,
+    pub max: i64,
+    pub mean: f64,
+
+    /// The lowest value with at least half of the outcomes at or below it.
+    pub median: i64,
+
+    /// The most likely value. When values are just as likely, this is the lowest.
+    pub mode: i64,
+
+    pub std_dev: f64,
+
+    /// How many rolls these were estimated from, if they weren't worked out exactly.
+    pub num_samples: Option<usize>,
+}
+
+impl Stats {
+    pub fn new(distribution: &Distribution) -> Self {
+        let probabilities = distribution
+            .probabilities()
+            .iter()
+            .map(|(&value, probability)| (value, probability.to_f64()))
+            .collect::<Vec<(i64, f64)>>();
+
+        Self::summarise(&probabilities, median(distribution))
+    }
+
+    /// Summarises how many times each value came up, out of `total` rolls.
+    /// The lowest and highest values are taken from the bounds when given,
+    /// as the rarest values may never have come up.
+    pub fn from_counts(
+        counts: &BTreeMap<i64, usize>,
+        total: usize,
+        bounds: Option<(i64, i64)>,
+    ) -> Self {
+        let probabilities = counts
+            .iter()
+            .map(|(&value, &count)| (value, count as f64 / total as f64))
+            .collect::<Vec<(i64, f64)>>();
+
+        let mut running_total = 0;
+        let median = counts.iter().find_map(|(&value, &count)| {
+            running_total += count;
+            if running_total * 2 >= total {
+                Some(value)
+            } else {
+                None
+            }
+        });
+
+        let stats = Self::summarise(&probabilities, median);
+        let (min, max) = bounds.unwrap_or((stats.min, stats.max));
+        Self {
+            min,
+            max,
+            num_samples: Some(total),
+            ..stats
+        }
+    }
+
+    fn summarise(probabilities: &[(i64, f64)], median: Option<i64>) -> Self {
+        let min = probabilities.first().map_or(0, |&(value, _)| value);
+        let max = probabilities.last().map_or(0, |&(value, _)| value);
+        let mean = probabilities
+            .iter()
+            .map(|&(value, probability)| value as f64 * probability)
+            .sum::<f64>();
+        let variance = probabilities
+            .iter()
+            .map(|&(value, probability)| (value as f64 - mean).powi(2) * probability)
+            .sum::<f64>();
+
+        Self {
+            min,
+            max,
+            mean,
+            median: median.unwrap_or(min),
+            mode: mode(probabilities).unwrap_or(min),
+            std_dev: variance.sqrt(),
+            num_samples: None,
+        }
+    }
+
+    pub fn variance(&self) -> f64 {
+        self.std_dev.powi(2)
+    }
+}
+
+/// The running total is kept exact where it fits, so that `1d10` has a median
+/// of `5` rather than whichever side of a half rounding puts it.
+fn median(distribution: &Distribution) -> Option<i64> {
+    let mut total = Ratio::zero();
+
+    for (&value, &probability) in distribution.probabilities() {
+        total = total + probability;
+        if total.is_at_least_half() {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+fn mode(probabilities: &[(i64, f64)]) -> Option<i64> {
+    let mut mode = None;
+
+    for &(value, probability) in probabilities {
+        match mode {
+            Some((_, highest)) if probability <= highest => {}
+            _ => mode = Some((value, probability)),
+        }
+    }
+
+    mode.map(|(value, _)| value)
+}
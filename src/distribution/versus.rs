@@ -0,0 +1,53 @@
+use crate::distribution::Odds;
+
+/// The odds of two expressions, and how often each comes out higher.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Versus {
+    pub left: Odds,
+    pub right: Odds,
+
+    /// The chance the left comes out higher than the right.
+    pub left_wins: f64,
+
+    /// The chance the right comes out higher than the left.
+    pub right_wins: f64,
+
+    /// The chance they both come out the same.
+    pub draw: f64,
+}
+
+impl Versus {
+    pub fn new(left: Odds, right: Odds) -> Self {
+        let right_probabilities = right.probabilities();
+
+        // The chance of the right coming out below each of its values.
+        let mut below = Vec::with_capacity(right_probabilities.len());
+        let mut total = 0.0;
+        for &(_, probability) in &right_probabilities {
+            below.push(total);
+            total += probability;
+        }
+
+        let mut left_wins = 0.0;
+        let mut draw = 0.0;
+        for (value, probability) in left.probabilities() {
+            let i = right_probabilities.partition_point(|&(right_value, _)| right_value < value);
+            let right_below = below.get(i).copied().unwrap_or(total);
+            left_wins += probability * right_below;
+
+            if let Some(&(right_value, right_probability)) = right_probabilities.get(i) {
+                if right_value == value {
+                    draw += probability * right_probability;
+                }
+            }
+        }
+
+        Self {
+            left,
+            right,
+            left_wins,
+            right_wins: (1.0 - left_wins - draw).max(0.0),
+            draw,
+        }
+    }
+}
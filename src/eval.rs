@@ -1,64 +1,840 @@
-use crate::ast::{Expr, ExprOp};
+use crate::ast::{Expr, ExprKind, ExprOp, RollModifier, Span, UnaryOp};
+use crate::distribution;
 
 use ::rand::rngs::OsRng;
 use ::rand::rngs::StdRng;
 use ::rand::RngCore;
 use ::rand::SeedableRng;
+use ::std::collections::BTreeMap;
 
-mod maths;
+mod error;
+pub(crate) mod function;
+mod limits;
+pub(crate) mod maths;
+pub(crate) mod modifier;
+mod output;
+mod simulation;
+mod source;
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum Output {
-    Integer(i64),
-}
+pub use self::error::{Error, ErrorKind};
+pub use self::function::{Arity, FUNCTIONS};
+pub use self::limits::{Limit, Limits};
+pub use self::output::{Breakdown, Chance, Die, Output, Roll, Sides, Value};
+pub use self::simulation::{Estimate, Simulation};
+pub use self::source::{DiceSource, Scripted};
+
+/// Rolls parsed expressions. The dice are seeded from the OS, unless given
+/// a seed, in which case the same seed always rolls the same dice. Any other
+/// source of dice can be given instead, such as scripted faces for tests.
+pub struct Eval<S = StdRng> {
+    source: S,
+    limits: Limits,
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum Error {
-    DivideByZero,
+    /// How deeply nested the expression being visited is.
+    depth: usize,
 
-    // todo, add float support and then add negative power support.
-    NegativePowerNotImplemented,
+    /// How many dice have been rolled so far, for the current expression.
+    num_dice: i64,
 }
 
-pub struct Eval {
-    rng: StdRng,
+impl Default for Eval {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Eval {
+    /// Rolls with the default limits.
     pub fn new() -> Self {
-        Self::new_with_seed(OsRng.next_u64())
+        Self::new_with_limits(Limits::default())
     }
 
+    /// Rolls the same dice each time for the same seed, with the default limits.
     pub fn new_with_seed(seed: u64) -> Self {
+        Self::new_with_seed_and_limits(seed, Limits::default())
+    }
+
+    /// Rolls dice seeded from the OS, with the limits given.
+    pub fn new_with_limits(limits: Limits) -> Self {
+        Self::new_with_seed_and_limits(OsRng.next_u64(), limits)
+    }
+
+    /// Rolls the same dice each time for the same seed, with the limits given.
+    pub fn new_with_seed_and_limits(seed: u64, limits: Limits) -> Self {
+        Self::new_with_source(StdRng::seed_from_u64(seed), limits)
+    }
+}
+
+impl<S: DiceSource> Eval<S> {
+    /// Rolls dice from the source, such as [`Scripted`] faces, with the limits given.
+    pub fn new_with_source(source: S, limits: Limits) -> Self {
         Self {
-            rng: StdRng::seed_from_u64(seed),
+            source,
+            limits,
+            depth: 0,
+            num_dice: 0,
         }
     }
 
+    /// Where the dice come from, such as to check how many scripted faces are left.
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    /// How much work each expression rolled is allowed to do.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Rolls the expression, keeping how each part of it was worked out.
     pub fn eval(&mut self, ast: Expr) -> Result<Output, Error> {
-        self.visit(ast).map(|total| Output::Integer(total))
+        self.depth = 0;
+        self.num_dice = 0;
+
+        self.visit(ast)
     }
 
-    fn visit(&mut self, ast: Expr) -> Result<i64, Error> {
-        match ast {
-            Expr::Integer(n) => Ok(n),
-            Expr::Operator(op, box left_expr, box right_expr) => {
+    /// Rolls the expression up to `num_samples` times, counting how often each
+    /// value comes up. This estimates the odds of expressions which are
+    /// too complex to work out exactly, such as `(1d4)d6!`.
+    ///
+    /// Each sample can roll as many dice as a single roll could, but it stops
+    /// early once `max_simulated_dice` have been rolled between them.
+    pub(crate) fn simulate(&mut self, ast: &Expr, num_samples: usize) -> Result<Simulation, Error> {
+        // The odds of a chance are the odds of its condition.
+        let ast = match &ast.kind {
+            ExprKind::Chance(condition) => condition,
+            _ => ast,
+        };
+
+        let mut counts = BTreeMap::new();
+        let mut num_rolled = 0;
+        let mut num_dice: i64 = 0;
+        while num_rolled < num_samples && num_dice < self.limits.max_simulated_dice {
+            let output = self.eval(ast.clone())?;
+            *counts.entry(output.value.to_i64()?).or_insert(0) += 1;
+
+            num_dice = num_dice.saturating_add(self.num_dice);
+            num_rolled += 1;
+        }
+
+        let bounds = distribution::bounds(ast, &self.limits);
+        Ok(Simulation::new(counts, num_rolled, bounds))
+    }
+
+    /// Errors are blamed on the innermost expression they came from.
+    fn visit(&mut self, ast: Expr) -> Result<Output, Error> {
+        let Expr { kind, span } = ast;
+
+        self.depth += 1;
+        if self.depth > self.limits.max_depth {
+            let limit = Limit::Depth(self.limits.max_depth);
+            return Err(Error::from(ErrorKind::LimitExceeded(limit)).at(span));
+        }
+
+        let output = match kind {
+            ExprKind::Integer(n) => Ok(Output {
+                value: Value::Integer(n),
+                breakdown: Breakdown::Integer(n),
+            }),
+            ExprKind::Operator(ExprOp::Roll(modifiers), box left_expr, box right_expr) => {
+                self.visit_roll(left_expr, right_expr, modifiers)
+            }
+            ExprKind::Operator(op, box left_expr, box right_expr) => {
                 self.visit_op(op, left_expr, right_expr)
             }
-        }
+            ExprKind::Unary(op, box expr) => self.visit_unary(op, expr),
+            ExprKind::Call(name, args) => self.visit_call(name, args),
+
+            // A die on its own is rolled once, like `d6`.
+            die @ (ExprKind::Fudge | ExprKind::Faces(_)) => {
+                let num_dice = Expr::new(ExprKind::Integer(1), Span::new(span.start, span.start));
+                self.visit_roll(num_dice, Expr::new(die, span), Vec::new())
+            }
+            ExprKind::Chance(box condition) => self.visit_chance(condition),
+        };
+
+        self.depth -= 1;
+        output.map_err(|err| err.at(span))
     }
 
-    fn visit_op(&mut self, op: ExprOp, left_expr: Expr, right_expr: Expr) -> Result<i64, Error> {
+    fn visit_op(&mut self, op: ExprOp, left_expr: Expr, right_expr: Expr) -> Result<Output, Error> {
         let left = self.visit(left_expr)?;
         let right = self.visit(right_expr)?;
+        let (l, r) = (left.value.to_i64()?, right.value.to_i64()?);
+
+        let total = maths::apply(&op, l, r)?;
+
+        // Adding to successes gives more successes, i.e. `10d10>=7 + 2`,
+        // and adding to a Fate roll stays on the ladder, i.e. `4dF + 2`.
+        // Comparing them only gives true or false.
+        let value = if matches!(op, ExprOp::Compare(_)) {
+            Value::Integer(total)
+        } else if left.value.is_successes() || right.value.is_successes() {
+            Value::Successes(total)
+        } else if left.value.is_fate() || right.value.is_fate() {
+            Value::Fate(total)
+        } else {
+            Value::Integer(total)
+        };
+
+        Ok(Output {
+            value,
+            breakdown: Breakdown::Operator(op, box left.breakdown, box right.breakdown),
+        })
+    }
+
+    /// Negating successes, or a Fate roll, keeps what kind of value it is.
+    fn visit_unary(&mut self, op: UnaryOp, expr: Expr) -> Result<Output, Error> {
+        let output = self.visit(expr)?;
+        let total = maths::apply_unary(&op, output.value.to_i64()?)?;
+
+        let value = match output.value {
+            Value::Successes(_) => Value::Successes(total),
+            Value::Fate(_) => Value::Fate(total),
+            _ => Value::Integer(total),
+        };
+
+        Ok(Output {
+            value,
+            breakdown: Breakdown::Unary(op, box output.breakdown),
+        })
+    }
+
+    /// The function is checked before its arguments are rolled, and like an
+    /// operator, successes or a Fate roll going in gives the same coming out.
+    fn visit_call(&mut self, name: String, args: Vec<Expr>) -> Result<Output, Error> {
+        let function = function::find(&name, args.len())?;
+        let outputs = args
+            .into_iter()
+            .map(|arg| self.visit(arg))
+            .collect::<Result<Vec<Output>, Error>>()?;
+
+        let values = outputs
+            .iter()
+            .map(|output| output.value.to_i64())
+            .collect::<Result<Vec<i64>, ErrorKind>>()?;
+        let total = function.apply(&values)?;
+
+        let value = if outputs.iter().any(|output| output.value.is_successes()) {
+            Value::Successes(total)
+        } else if outputs.iter().any(|output| output.value.is_fate()) {
+            Value::Fate(total)
+        } else {
+            Value::Integer(total)
+        };
+
+        Ok(Output {
+            value,
+            breakdown: Breakdown::Call(
+                name,
+                outputs.into_iter().map(|output| output.breakdown).collect(),
+            ),
+        })
+    }
+
+    fn visit_roll(
+        &mut self,
+        left_expr: Expr,
+        right_expr: Expr,
+        modifiers: Vec<RollModifier>,
+    ) -> Result<Output, Error> {
+        let num_dice = self.visit(left_expr)?;
+        let (sides, sides_breakdown) = self.visit_sides(right_expr)?;
+        let n = num_dice.value.to_i64()?;
+
+        self.num_dice = self.num_dice.saturating_add(n.max(0));
+        if self.num_dice > self.limits.max_dice {
+            return Err(ErrorKind::LimitExceeded(Limit::Dice(self.limits.max_dice)).into());
+        }
+
+        let mut dice = maths::roll(&mut self.source, n, &sides)?
+            .into_iter()
+            .map(Die::new)
+            .collect::<Vec<Die>>();
+
+        for modifier in &modifiers {
+            modifier::apply(&mut self.source, &self.limits, &mut dice, &sides, modifier)?;
+        }
+
+        let roll = Roll {
+            num_dice: n,
+            sides,
+            modifiers,
+            dice,
+        };
 
-        Ok(match op {
-            ExprOp::Add => maths::add(left, right)?,
-            ExprOp::Sub => maths::sub(left, right)?,
-            ExprOp::Mult => maths::mult(left, right)?,
-            ExprOp::Div => maths::divide(left, right)?,
-            ExprOp::Pow => maths::power(left, right)?,
-            ExprOp::Roll => maths::roll(&mut self.rng, left, right)?,
+        Ok(Output {
+            value: roll.value()?,
+            breakdown: Breakdown::Roll(box num_dice.breakdown, box sides_breakdown, roll),
         })
     }
+
+    /// Chances are worked out from the odds of the condition, rather than rolled.
+    /// Estimating them rolls with this eval, so it's put back as it was after.
+    fn visit_chance(&mut self, condition: Expr) -> Result<Output, Error> {
+        let (depth, num_dice) = (self.depth, self.num_dice);
+        let odds = distribution::odds_with_eval(&condition, self);
+        self.depth = depth;
+        self.num_dice = num_dice;
+
+        let chance = odds?.chance();
+
+        Ok(Output {
+            value: Value::Chance(chance),
+            breakdown: Breakdown::Chance(chance),
+        })
+    }
+
+    fn visit_sides(&mut self, ast: Expr) -> Result<(Sides, Breakdown), Error> {
+        let span = ast.span;
+        let (sides, breakdown) = match ast.kind {
+            ExprKind::Fudge => (Sides::Fudge, Breakdown::Sides(Sides::Fudge)),
+            ExprKind::Faces(faces) => {
+                let sides = Sides::Faces(faces);
+                (sides.clone(), Breakdown::Sides(sides))
+            }
+            _ => {
+                let output = self.visit(ast)?;
+                (Sides::Number(output.value.to_i64()?), output.breakdown)
+            }
+        };
+
+        if maths::num_faces(&sides) > self.limits.max_sides {
+            let limit = Limit::Sides(self.limits.max_sides);
+            return Err(Error::from(ErrorKind::LimitExceeded(limit)).at(span));
+        }
+
+        Ok((sides, breakdown))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn it_should_keep_the_highest_dice() {
+        let output = test_eval("4d6kh3").unwrap();
+        let kept = sorted_values(output.rolls()[0], true);
+        let dropped = sorted_values(output.rolls()[0], false);
+
+        assert_eq!(kept.len(), 3);
+        assert_eq!(dropped.len(), 1);
+        assert!(dropped[0] <= kept[0]);
+        assert_eq!(output.value, Value::Integer(kept.iter().sum()));
+    }
+
+    #[test]
+    fn it_should_explode_dice_which_roll_their_maximum() {
+        let output = test_eval("10d2!").unwrap();
+        let dice = &output.rolls()[0].dice;
+
+        for (i, die) in dice.iter().enumerate() {
+            assert_eq!(die.is_exploded, die.value == 2);
+            if die.is_exploded {
+                assert!(i + 1 < dice.len());
+            }
+        }
+        assert_eq!(dice.iter().filter(|die| !die.is_exploded).count(), 10);
+    }
+
+    #[test]
+    fn it_should_compound_exploding_dice_into_one_die() {
+        let output = test_eval("10d2!!").unwrap();
+        let dice = &output.rolls()[0].dice;
+
+        assert_eq!(dice.len(), 10);
+        for die in dice {
+            assert_eq!(die.is_compounded, die.value > 1);
+            assert!(!die.is_compounded || die.value % 2 == 1);
+        }
+    }
+
+    #[test]
+    fn it_should_take_one_off_penetrating_dice() {
+        let output = test_eval("20d2!p").unwrap();
+        let dice = &output.rolls()[0].dice;
+
+        for (i, die) in dice.iter().enumerate() {
+            let is_extra_die = i > 0 && dice[i - 1].is_exploded;
+            if is_extra_die {
+                assert!(die.value == 0 || die.value == 1);
+                assert_eq!(die.is_exploded, die.value == 1);
+            } else {
+                assert_eq!(die.is_exploded, die.value == 2);
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_stop_dice_exploding_forever() {
+        let too_many_explosions = Err(ErrorKind::LimitExceeded(Limit::Explosions(100)));
+
+        assert_eq!(kind(test_eval("1d1!")), too_many_explosions);
+        assert_eq!(kind(test_eval("1d1!!")), too_many_explosions);
+        assert_eq!(kind(test_eval("1d1!p")), too_many_explosions);
+    }
+
+    #[test]
+    fn it_should_reroll_until_dice_stop_matching() {
+        let output = test_eval("20d6r<3").unwrap();
+        let dice = &output.rolls()[0].dice;
+
+        for (i, die) in dice.iter().enumerate() {
+            assert_eq!(die.is_rerolled, die.value <= 3);
+            assert_eq!(die.is_kept, !die.is_rerolled);
+            if die.is_rerolled {
+                assert!(i + 1 < dice.len());
+            }
+        }
+        assert_eq!(dice.iter().filter(|die| die.is_kept).count(), 20);
+    }
+
+    #[test]
+    fn it_should_reroll_once() {
+        let output = test_eval("20d6ro<6").unwrap();
+        let dice = &output.rolls()[0].dice;
+
+        for (i, die) in dice.iter().enumerate() {
+            if die.is_rerolled {
+                assert!(!dice[i + 1].is_rerolled);
+                assert!(dice[i + 1].is_kept);
+            }
+        }
+        assert_eq!(dice.iter().filter(|die| die.is_kept).count(), 20);
+    }
+
+    #[test]
+    fn it_should_stop_dice_rerolling_forever() {
+        assert_eq!(
+            kind(test_eval("1d6r<7")),
+            Err(ErrorKind::LimitExceeded(Limit::Rerolls(100)))
+        );
+    }
+
+    #[test]
+    fn it_should_limit_the_number_of_dice() {
+        let too_many_dice = Err(ErrorKind::LimitExceeded(Limit::Dice(10_000)));
+
+        assert_eq!(kind(test_eval("9999999999999d6")), too_many_dice);
+        assert_eq!(kind(test_eval("6000d6 + 6000d6")), too_many_dice);
+        assert!(test_eval("5000d6 + 5000d6").is_ok());
+    }
+
+    #[test]
+    fn it_should_limit_the_number_of_sides() {
+        let limits = Limits {
+            max_sides: 100,
+            ..Limits::default()
+        };
+        let mut eval = Eval::new_with_seed_and_limits(0, limits);
+        let too_many_sides = Err(ErrorKind::LimitExceeded(Limit::Sides(100)));
+
+        assert_eq!(kind(eval.eval(parse_one("1d101"))), too_many_sides);
+        assert_eq!(kind(eval.eval(parse_one("1d-101"))), too_many_sides);
+        assert!(eval.eval(parse_one("1d100")).is_ok());
+    }
+
+    #[test]
+    fn it_should_limit_how_deeply_expressions_are_nested() {
+        let limits = Limits {
+            max_depth: 2,
+            ..Limits::default()
+        };
+        let mut eval = Eval::new_with_seed_and_limits(0, limits);
+
+        assert_eq!(
+            kind(eval.eval(parse_one("1 + 2 + 3"))),
+            Err(ErrorKind::LimitExceeded(Limit::Depth(2)))
+        );
+        assert!(eval.eval(parse_one("1 + 2")).is_ok());
+    }
+
+    #[test]
+    fn it_should_count_successes() {
+        let output = test_eval("10d10>=7").unwrap();
+        let dice = &output.rolls()[0].dice;
+        let num_successes = dice.iter().filter(|die| die.value >= 7).count();
+
+        assert_eq!(output.value, Value::Successes(num_successes as i64));
+    }
+
+    #[test]
+    fn it_should_take_failures_off_successes() {
+        assert_eq!(test_eval("3d1>=1").unwrap().value, Value::Successes(3));
+        assert_eq!(test_eval("3d1>=1f1").unwrap().value, Value::Successes(0));
+        assert_eq!(test_eval("3d1f1").unwrap().value, Value::Successes(-3));
+    }
+
+    #[test]
+    fn it_should_add_to_successes() {
+        assert_eq!(test_eval("3d1>=1 + 2").unwrap().value, Value::Successes(5));
+        assert_eq!(test_eval("(3d1>=1)d1").unwrap().value, Value::Integer(3));
+    }
+
+    #[test]
+    fn it_should_roll_fudge_dice_on_the_fate_ladder() {
+        let output = test_eval("4dF + 2").unwrap();
+        let dice = &output.rolls()[0].dice;
+        let total = dice.iter().map(|die| die.value).sum::<i64>();
+
+        assert!(dice.iter().all(|die| (-1..=1).contains(&die.value)));
+        assert_eq!(output.value, Value::Fate(total + 2));
+    }
+
+    #[test]
+    fn it_should_roll_dice_with_custom_faces() {
+        let output = test_eval("20d{2,3,3,4}").unwrap();
+        let dice = &output.rolls()[0].dice;
+
+        assert!(dice.iter().all(|die| (2..=4).contains(&die.value)));
+        assert_eq!(test_eval("3d{5}").unwrap().value, Value::Integer(15));
+    }
+
+    #[test]
+    fn it_should_work_out_operators_in_order() {
+        assert_eq!(test_eval("10 - 2 - 3").unwrap().value, Value::Integer(5));
+        assert_eq!(test_eval("100 / 10 / 2").unwrap().value, Value::Integer(5));
+        assert_eq!(test_eval("2 ^ 3 ^ 2").unwrap().value, Value::Integer(512));
+    }
+
+    #[test]
+    fn it_should_round_division_each_way() {
+        let cases = [
+            ("7 / 2", 3),
+            ("-7 / 2", -3),
+            ("7 // 2", 3),
+            ("-7 // 2", -4),
+            ("7 /^ 2", 4),
+            ("-7 /^ 2", -3),
+            ("7 /~ 2", 4),
+            ("-7 /~ 2", -4),
+            ("7 /~ 3", 2),
+            ("8 /~ -3", -3),
+            ("7 % 3", 1),
+            ("-7 % 3", 2),
+            ("7 % -3", -2),
+        ];
+
+        for (input, expected) in cases {
+            let value = test_eval(input).unwrap().value;
+            assert_eq!(value, Value::Integer(expected), "{}", input);
+        }
+        assert_eq!(kind(test_eval("7 % 0")), Err(ErrorKind::DivideByZero));
+    }
+
+    #[test]
+    fn it_should_call_functions() {
+        let cases = [
+            ("max(1, 1d1 - 1)", 1),
+            ("min(3, 2, 5)", 2),
+            ("abs(-3)", 3),
+            ("floor(-7, 2)", -4),
+            ("ceil(7, 2)", 4),
+            ("round(5, 2)", 3),
+            ("clamp(2d6 + 20, 2, 12)", 12),
+        ];
+
+        for (input, expected) in cases {
+            let value = test_eval(input).unwrap().value;
+            assert_eq!(value, Value::Integer(expected), "{}", input);
+        }
+
+        let value = test_eval("max(3d1>=1, 1)").unwrap().value;
+        assert_eq!(value, Value::Successes(3));
+    }
+
+    #[test]
+    fn it_should_error_when_functions_are_called_wrongly() {
+        assert_eq!(
+            kind(test_eval("dmg(3)")),
+            Err(ErrorKind::UnknownFunction("dmg".to_string()))
+        );
+        assert_eq!(
+            kind(test_eval("clamp(1d6, 2)")),
+            Err(ErrorKind::WrongArity("clamp", Arity::Exactly(3), 2))
+        );
+        assert_eq!(
+            kind(test_eval("max()")),
+            Err(ErrorKind::WrongArity("max", Arity::AtLeast(1), 0))
+        );
+    }
+
+    #[test]
+    fn it_should_negate_values() {
+        assert_eq!(test_eval("-2 ^ 2").unwrap().value, Value::Integer(-4));
+        assert_eq!(test_eval("(-2) ^ 2").unwrap().value, Value::Integer(4));
+        assert_eq!(test_eval("1 - -2").unwrap().value, Value::Integer(3));
+        assert_eq!(test_eval("+3").unwrap().value, Value::Integer(3));
+        assert_eq!(test_eval("-3d1>=1").unwrap().value, Value::Successes(-3));
+        assert_eq!(
+            kind(test_eval("-(-9223372036854775807 - 1)")),
+            Err(ErrorKind::Overflow(ExprOp::Sub, 0, i64::MIN))
+        );
+    }
+
+    #[test]
+    fn it_should_compare_values() {
+        assert_eq!(test_eval("2 > 1").unwrap().value, Value::Integer(1));
+        assert_eq!(test_eval("2 < 1").unwrap().value, Value::Integer(0));
+        assert_eq!(test_eval("3d1>=1 == 3").unwrap().value, Value::Integer(1));
+    }
+
+    #[test]
+    fn it_should_blame_the_expression_which_failed() {
+        let input = "1d6 + 2 / (3 - 3)";
+        let err = test_eval(input).unwrap_err();
+
+        assert_eq!(err.kind, ErrorKind::DivideByZero);
+        assert_eq!(err.span.unwrap().text(input), "2 / (3 - 3)");
+    }
+
+    #[test]
+    fn it_should_work_out_chances() {
+        let beats_fifteen = test_eval("P(1d20 + 5 >= 15)").unwrap();
+        let beats_six = test_eval("chance 1d6 > 6").unwrap();
+
+        assert_eq!(beats_fifteen.value, Value::Chance(Chance::Exact(0.55)));
+        assert_eq!(beats_six.value, Value::Chance(Chance::Exact(0.0)));
+    }
+
+    #[test]
+    fn it_should_give_the_range_of_estimated_chances() {
+        let output = test_eval("P(2d6! > 7)").unwrap();
+        match output.value {
+            Value::Chance(Chance::Estimated(estimate, num_samples)) => {
+                assert_eq!(num_samples, 10_000);
+                assert!(estimate.low < estimate.probability);
+                assert!(estimate.probability < estimate.high);
+            }
+            ref value => panic!("expected an estimated chance, got {:?}", value),
+        }
+        assert!(format!("{}", output).ends_with(", estimated from 10000 rolls)"));
+    }
+
+    #[test]
+    fn it_should_error_rather_than_use_a_nested_chance() {
+        let chance = Expr::chance(parse_one("1d6 > 3"));
+        let added = Expr::operator(ExprOp::Add, Expr::integer(1), chance.clone());
+        let as_sides = Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), chance);
+        let mut eval = Eval::new_with_seed(0);
+
+        assert_eq!(kind(eval.eval(added)), Err(ErrorKind::NestedChance));
+        assert_eq!(kind(eval.eval(as_sides)), Err(ErrorKind::NestedChance));
+    }
+
+    #[test]
+    fn it_should_give_impossible_chances_no_chance() {
+        let output = test_eval("P(2d6 > 12)").unwrap();
+
+        assert!(matches!(
+            output.value,
+            Value::Chance(chance) if chance.probability().is_sign_positive()
+        ));
+        assert_eq!(format!("{}", output), "0.00%");
+    }
+
+    #[test]
+    fn it_should_error_when_numbers_overflow() {
+        assert_eq!(
+            kind(test_eval("9999999999^9")),
+            Err(ErrorKind::Overflow(ExprOp::Pow, 9999999999, 9))
+        );
+        assert_eq!(
+            kind(test_eval("99999999999*99999999999")),
+            Err(ErrorKind::Overflow(ExprOp::Mult, 99999999999, 99999999999))
+        );
+        assert_eq!(
+            kind(test_eval("9223372036854775807 + 1")),
+            Err(ErrorKind::Overflow(ExprOp::Add, 9223372036854775807, 1))
+        );
+        assert_eq!(
+            kind(test_eval("-9223372036854775807 - 2")),
+            Err(ErrorKind::Overflow(ExprOp::Sub, -9223372036854775807, 2))
+        );
+    }
+
+    #[test]
+    fn it_should_error_when_dice_totals_overflow() {
+        assert_eq!(
+            kind(test_eval("2d{9223372036854775807}")),
+            Err(ErrorKind::Overflow(ExprOp::Add, i64::MAX, i64::MAX))
+        );
+    }
+
+    #[test]
+    fn it_should_raise_one_to_huge_powers() {
+        let one = test_eval("1^99999999999").unwrap();
+        let minus_one = test_eval("(0-1)^99999999999").unwrap();
+
+        assert_eq!(one.value, Value::Integer(1));
+        assert_eq!(minus_one.value, Value::Integer(-1));
+    }
+
+    #[test]
+    fn it_should_simulate_rolling_many_times() {
+        let ast = parse_one("2d6");
+        let simulation = Eval::new_with_seed(0).simulate(&ast, 10_000).unwrap();
+        let estimates = simulation.estimates();
+        let values = estimates
+            .iter()
+            .map(|&(value, _)| value)
+            .collect::<Vec<i64>>();
+        let seven = estimates[5].1;
+
+        assert_eq!(simulation.num_samples(), 10_000);
+        assert_eq!(values, (2..=12).collect::<Vec<i64>>());
+        assert!(seven.low < 1.0 / 6.0 && 1.0 / 6.0 < seven.high);
+    }
+
+    #[test]
+    fn it_should_stop_simulating_once_out_of_dice() {
+        let limits = Limits {
+            max_dice: 100,
+            max_simulated_dice: 1_000,
+            ..Limits::default()
+        };
+        let mut eval = Eval::new_with_seed_and_limits(0, limits);
+        let simulation = eval.simulate(&parse_one("10d6"), 10_000).unwrap();
+
+        assert_eq!(simulation.num_samples(), 100);
+        assert_eq!(
+            kind(eval.simulate(&parse_one("101d6"), 10_000)),
+            Err(ErrorKind::LimitExceeded(Limit::Dice(100)))
+        );
+    }
+
+    #[test]
+    fn it_should_simulate_exploding_dice() {
+        let ast = parse_one("1d6!");
+        let simulation = Eval::new_with_seed(0).simulate(&ast, 1_000).unwrap();
+
+        assert!(simulation
+            .estimates()
+            .iter()
+            .all(|&(value, _)| value >= 1 && value != 6));
+    }
+
+    #[test]
+    fn it_should_roll_scripted_faces() {
+        let mut eval = Eval::new_with_source(Scripted::new(vec![6, 5, 3, 1]), Limits::default());
+        let output = eval.eval(parse_one("4d6kh3")).unwrap();
+        let dice = &output.rolls()[0].dice;
+
+        assert_eq!(output.value, Value::Integer(14));
+        assert_eq!(
+            dice.iter().map(|die| die.value).collect::<Vec<i64>>(),
+            vec![6, 5, 3, 1]
+        );
+        assert!(!dice[3].is_kept);
+        assert_eq!(output.rolls()[0].values(), vec![6, 5, 3]);
+        assert_eq!(eval.source().remaining(), 0);
+    }
+
+    #[test]
+    fn it_should_explode_scripted_faces() {
+        let mut eval = Eval::new_with_source(Scripted::new(vec![6, 2, 4]), Limits::default());
+        let output = eval.eval(parse_one("2d6!")).unwrap();
+        let dice = &output.rolls()[0].dice;
+
+        // Both dice are rolled before the first explodes, into the die after it.
+        assert_eq!(output.value, Value::Integer(12));
+        assert_eq!(
+            dice.iter().map(|die| die.value).collect::<Vec<i64>>(),
+            vec![6, 4, 2]
+        );
+        assert!(dice[0].is_exploded);
+        assert!(!dice[1].is_exploded);
+    }
+
+    #[test]
+    fn it_should_error_when_scripted_faces_run_out() {
+        let mut eval = Eval::new_with_source(Scripted::new(vec![6]), Limits::default());
+
+        assert_eq!(
+            kind(eval.eval(parse_one("2d6"))),
+            Err(ErrorKind::OutOfFaces)
+        );
+    }
+
+    #[test]
+    fn it_should_estimate_chances_with_its_own_dice() {
+        let ast = parse_one("P(1d6! > 6)");
+        let first = Eval::new_with_seed(7).eval(ast.clone()).unwrap();
+        let second = Eval::new_with_seed(7).eval(ast.clone()).unwrap();
+
+        assert_eq!(first.value, second.value);
+
+        let mut eval = Eval::new_with_source(Scripted::new(vec![6, 1]), Limits::default());
+
+        assert_eq!(kind(eval.eval(ast)), Err(ErrorKind::OutOfFaces));
+        assert_eq!(eval.source().remaining(), 0);
+    }
+
+    #[test]
+    fn it_should_format_a_roll_with_its_dice() {
+        let with_dice = format_scripted("2d6 + 4", vec![3, 5]);
+        let without_dice = format_scripted("1 + 2", vec![]);
+
+        assert_eq!(with_dice, "2d6 [3, 5] + 4 = 12");
+        assert_eq!(without_dice, "3");
+    }
+
+    #[test]
+    fn it_should_format_dropped_dice_struck_through() {
+        let tests = [
+            ("4d6kh3", vec![6, 5, 3, 1], "4d6kh3 [6, 5, 3, ~1~] = 14"),
+            ("4d6dl1", vec![2, 6, 1, 4], "4d6dl1 [2, 6, ~1~, 4] = 12"),
+            ("2d6!", vec![6, 2, 4], "2d6! [6!, 4, 2] = 12"),
+        ];
+
+        for (expr, faces, expected) in tests {
+            assert_eq!(format_scripted(expr, faces), expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn it_should_format_nested_rolls_in_brackets() {
+        let tests = [
+            ("(1d4)d6", vec![2, 3, 4], "(1d4 [2])d6 [3, 4] = 7"),
+            ("1d(2d4)", vec![1, 3, 2], "1d(2d4 [1, 3]) [2] = 2"),
+            ("(1d4 + 1)d6", vec![1, 6, 5], "(1d4 [1] + 1)d6 [6, 5] = 11"),
+        ];
+
+        for (expr, faces, expected) in tests {
+            assert_eq!(format_scripted(expr, faces), expected, "{}", expr);
+        }
+    }
+
+    fn kind<T>(result: Result<T, Error>) -> Result<T, ErrorKind> {
+        result.map_err(|err| err.kind)
+    }
+
+    fn format_scripted(expr: &str, faces: Vec<i64>) -> String {
+        let mut eval = Eval::new_with_source(Scripted::new(faces), Limits::default());
+
+        format!("{}", eval.eval(parse_one(expr)).unwrap())
+    }
+
+    fn test_eval(expr: &str) -> Result<Output, Error> {
+        Eval::new_with_seed(0).eval(parse_one(expr))
+    }
+
+    fn parse_one(expr: &str) -> Expr {
+        parse(expr).unwrap().remove(0)
+    }
+
+    fn sorted_values(roll: &Roll, is_kept: bool) -> Vec<i64> {
+        let mut values = roll
+            .dice
+            .iter()
+            .filter(|die| die.is_kept == is_kept)
+            .map(|die| die.value)
+            .collect::<Vec<i64>>();
+        values.sort();
+
+        values
+    }
 }
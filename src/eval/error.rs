@@ -1,19 +1,66 @@
+use crate::ast::{ExprOp, Span};
+use crate::eval::{Arity, Limit};
 
-use ::std::fmt::{Display, Result, Formatter};
-
+/// Why an expression couldn't be rolled, along with the part of it to blame.
 #[derive(Clone, Debug, PartialEq)]
-pub enum Error {
-  DivideByZero,
+pub struct Error {
+    /// What went wrong.
+    pub kind: ErrorKind,
+
+    /// The innermost expression which failed, such as the `2 / 0` in
+    /// `1d6 + 2 / 0`. This is only missing when there was no expression,
+    /// such as for errors from maths done on its own.
+    pub span: Option<Span>,
+}
+
+impl Error {
+    /// An error which isn't yet blamed on any expression.
+    pub fn new(kind: ErrorKind) -> Self {
+        Self { kind, span: None }
+    }
 
-  // todo, add float support and then add negative power support.
-  NegativePowerNotImplemented,
+    /// Blames the expression at the span, unless an expression inside it
+    /// was already blamed.
+    pub fn at(self, span: Span) -> Self {
+        Self {
+            span: self.span.or(Some(span)),
+            ..self
+        }
+    }
 }
 
-impl Display for Error {
-  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-    match self {
-      Self::DivideByZero => write!(f, "divide by zero"),
-      Self::NegativePowerNotImplemented => write!(f, "negative powers are not implemented"),
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind)
     }
-  }
+}
+
+/// What went wrong when rolling an expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ErrorKind {
+    /// The right of a division, or of `%`, was zero.
+    DivideByZero,
+
+    /// A number was raised to a negative power, which has no whole result.
+    // todo, add float support and then add negative power support.
+    NegativePowerNotImplemented,
+
+    /// The result of the operator, on the left and right values, is too big to fit.
+    Overflow(ExprOp, i64, i64),
+
+    /// The expression needed more work than the [`Limits`](crate::Limits) allow.
+    LimitExceeded(Limit),
+
+    /// A chance was used within another expression, such as by building
+    /// one with `Expr::chance` and adding to it.
+    NestedChance,
+
+    /// More dice were rolled than a scripted source had faces for.
+    OutOfFaces,
+
+    /// There's no function with the name.
+    UnknownFunction(String),
+
+    /// The function, which takes the arity, was given the wrong number of arguments.
+    WrongArity(&'static str, Arity, usize),
 }
@@ -0,0 +1,109 @@
+use crate::eval::maths;
+use crate::eval::ErrorKind;
+
+/// The functions which can be called in expressions, i.e. `max(1, 1d4 - 1)`.
+pub const FUNCTIONS: &[Function] = &[
+    Function::new("min", Arity::AtLeast(1), min),
+    Function::new("max", Arity::AtLeast(1), max),
+    Function::new("abs", Arity::Exactly(1), abs),
+    Function::new("floor", Arity::Exactly(2), floor),
+    Function::new("ceil", Arity::Exactly(2), ceil),
+    Function::new("round", Arity::Exactly(2), round),
+    Function::new("clamp", Arity::Exactly(3), clamp),
+];
+
+/// How many arguments a function can be given.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arity {
+    /// Exactly this many arguments, i.e. `abs` takes one.
+    Exactly(usize),
+
+    /// This many arguments or more, i.e. `max` takes at least one.
+    AtLeast(usize),
+}
+
+impl Arity {
+    /// Whether a function can be given this many arguments.
+    pub fn allows(&self, num_args: usize) -> bool {
+        match *self {
+            Self::Exactly(n) => num_args == n,
+            Self::AtLeast(n) => num_args >= n,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Function {
+    pub name: &'static str,
+    pub arity: Arity,
+    apply: fn(&[i64]) -> Result<i64, ErrorKind>,
+}
+
+impl Function {
+    const fn new(
+        name: &'static str,
+        arity: Arity,
+        apply: fn(&[i64]) -> Result<i64, ErrorKind>,
+    ) -> Self {
+        Self { name, arity, apply }
+    }
+
+    /// Calls the function, which must be given as many arguments as its arity allows.
+    pub fn apply(&self, args: &[i64]) -> Result<i64, ErrorKind> {
+        (self.apply)(args)
+    }
+}
+
+/// Finds the function with the name, which can be given this many arguments.
+pub fn find(name: &str, num_args: usize) -> Result<&'static Function, ErrorKind> {
+    let function = FUNCTIONS
+        .iter()
+        .find(|function| function.name == name)
+        .ok_or_else(|| ErrorKind::UnknownFunction(name.to_string()))?;
+
+    if function.arity.allows(num_args) {
+        Ok(function)
+    } else {
+        Err(ErrorKind::WrongArity(
+            function.name,
+            function.arity,
+            num_args,
+        ))
+    }
+}
+
+fn min(args: &[i64]) -> Result<i64, ErrorKind> {
+    Ok(args.iter().copied().fold(i64::MAX, i64::min))
+}
+
+fn max(args: &[i64]) -> Result<i64, ErrorKind> {
+    Ok(args.iter().copied().fold(i64::MIN, i64::max))
+}
+
+fn abs(args: &[i64]) -> Result<i64, ErrorKind> {
+    if args[0] < 0 {
+        maths::negate(args[0])
+    } else {
+        Ok(args[0])
+    }
+}
+
+/// Values are always whole numbers, so rounding is done as part of dividing,
+/// i.e. `floor(7, 2)` is `7 // 2`.
+fn floor(args: &[i64]) -> Result<i64, ErrorKind> {
+    maths::floor_divide(args[0], args[1])
+}
+
+fn ceil(args: &[i64]) -> Result<i64, ErrorKind> {
+    maths::ceil_divide(args[0], args[1])
+}
+
+fn round(args: &[i64]) -> Result<i64, ErrorKind> {
+    maths::round_divide(args[0], args[1])
+}
+
+/// Keeps the value between the lowest and highest, with the highest winning
+/// if they're the wrong way around.
+fn clamp(args: &[i64]) -> Result<i64, ErrorKind> {
+    Ok(args[0].max(args[1]).min(args[2]))
+}
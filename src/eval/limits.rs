@@ -0,0 +1,66 @@
+/// How much work a single evaluation is allowed to do, so that expressions
+/// such as `9999999999999d6` are turned away rather than rolled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+    /// The most dice which may be rolled, across the whole expression.
+    pub max_dice: i64,
+
+    /// The most sides, or faces, a single die may have.
+    pub max_sides: i64,
+
+    /// How many times a single die may explode in a row.
+    pub max_explosions: usize,
+
+    /// How many times a single die may be rerolled.
+    pub max_rerolls: usize,
+
+    /// How deeply an expression may be nested.
+    pub max_depth: usize,
+
+    /// The most combinations of outcomes which may be worked through,
+    /// when finding the exact odds of an expression.
+    pub max_combinations: usize,
+
+    /// The most dice which may be rolled across every sample, when estimating
+    /// the odds of an expression by rolling it many times.
+    pub max_simulated_dice: i64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_dice: 10_000,
+            max_sides: 1_000_000_000,
+            max_explosions: 100,
+            max_rerolls: 100,
+            max_depth: 1_000,
+            max_combinations: 1_000_000,
+            max_simulated_dice: 10_000_000,
+        }
+    }
+}
+
+/// The limit which was exceeded, along with its maximum.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Limit {
+    /// [`Limits::max_dice`]
+    Dice(i64),
+
+    /// [`Limits::max_sides`]
+    Sides(i64),
+
+    /// [`Limits::max_explosions`]
+    Explosions(usize),
+
+    /// [`Limits::max_rerolls`]
+    Rerolls(usize),
+
+    /// [`Limits::max_depth`]
+    Depth(usize),
+
+    /// [`Limits::max_combinations`]
+    Combinations(usize),
+
+    /// [`Limits::max_simulated_dice`]
+    SimulatedDice(i64),
+}
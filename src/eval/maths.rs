@@ -1,51 +1,218 @@
 use ::rand::Rng;
 
-use crate::eval::Error;
+use crate::ast::{Comparison, ExprOp, UnaryOp};
+use crate::eval::{DiceSource, ErrorKind, Sides};
 
-pub fn add(left: i64, right: i64) -> Result<i64, Error> {
-    Ok(left + right)
+pub fn apply(op: &ExprOp, left: i64, right: i64) -> Result<i64, ErrorKind> {
+    match op {
+        ExprOp::Add => add(left, right),
+        ExprOp::Sub => sub(left, right),
+        ExprOp::Mult => mult(left, right),
+        ExprOp::Div => divide(left, right),
+        ExprOp::FloorDiv => floor_divide(left, right),
+        ExprOp::CeilDiv => ceil_divide(left, right),
+        ExprOp::RoundDiv => round_divide(left, right),
+        ExprOp::Mod => modulo(left, right),
+        ExprOp::Pow => power(left, right),
+        ExprOp::Roll(_) => unreachable!("rolls are not worked out as maths"),
+        ExprOp::Compare(comparison) => Ok(compare(comparison, left, right)),
+    }
+}
+
+pub fn apply_unary(op: &UnaryOp, n: i64) -> Result<i64, ErrorKind> {
+    match op {
+        UnaryOp::Neg => negate(n),
+        UnaryOp::Pos => Ok(n),
+    }
+}
+
+/// Gives `1` when the comparison is true, and `0` when it's false.
+pub fn compare(comparison: &Comparison, left: i64, right: i64) -> i64 {
+    let is_true = match comparison {
+        Comparison::Equal => left == right,
+        Comparison::NotEqual => left != right,
+        Comparison::Less => left < right,
+        Comparison::LessOrEqual => left <= right,
+        Comparison::Greater => left > right,
+        Comparison::GreaterOrEqual => left >= right,
+    };
+
+    is_true as i64
+}
+
+pub fn add(left: i64, right: i64) -> Result<i64, ErrorKind> {
+    left.checked_add(right)
+        .ok_or(ErrorKind::Overflow(ExprOp::Add, left, right))
+}
+
+pub fn sub(left: i64, right: i64) -> Result<i64, ErrorKind> {
+    left.checked_sub(right)
+        .ok_or(ErrorKind::Overflow(ExprOp::Sub, left, right))
+}
+
+/// Negating is taking away from zero, so it overflows the same way.
+pub fn negate(n: i64) -> Result<i64, ErrorKind> {
+    n.checked_neg()
+        .ok_or(ErrorKind::Overflow(ExprOp::Sub, 0, n))
+}
+
+pub fn mult(left: i64, right: i64) -> Result<i64, ErrorKind> {
+    left.checked_mul(right)
+        .ok_or(ErrorKind::Overflow(ExprOp::Mult, left, right))
+}
+
+pub fn divide(left: i64, right: i64) -> Result<i64, ErrorKind> {
+    let (quotient, _) = div_rem(ExprOp::Div, left, right)?;
+    Ok(quotient)
+}
+
+pub fn floor_divide(left: i64, right: i64) -> Result<i64, ErrorKind> {
+    let (quotient, remainder) = div_rem(ExprOp::FloorDiv, left, right)?;
+    if remainder != 0 && (remainder < 0) != (right < 0) {
+        Ok(quotient - 1)
+    } else {
+        Ok(quotient)
+    }
+}
+
+pub fn ceil_divide(left: i64, right: i64) -> Result<i64, ErrorKind> {
+    let (quotient, remainder) = div_rem(ExprOp::CeilDiv, left, right)?;
+    if remainder != 0 && (remainder < 0) == (right < 0) {
+        Ok(quotient + 1)
+    } else {
+        Ok(quotient)
+    }
 }
 
-pub fn sub(left: i64, right: i64) -> Result<i64, Error> {
-    Ok(left - right)
+/// Halves are rounded away from zero, i.e. `5 /~ 2` is `3` and `-5 /~ 2` is `-3`.
+pub fn round_divide(left: i64, right: i64) -> Result<i64, ErrorKind> {
+    let (quotient, remainder) = div_rem(ExprOp::RoundDiv, left, right)?;
+    let (remainder_size, right_size) = (remainder.unsigned_abs(), right.unsigned_abs());
+    if remainder == 0 || remainder_size < right_size - remainder_size {
+        Ok(quotient)
+    } else if (remainder < 0) == (right < 0) {
+        Ok(quotient + 1)
+    } else {
+        Ok(quotient - 1)
+    }
 }
 
-pub fn mult(left: i64, right: i64) -> Result<i64, Error> {
-    Ok(left * right)
+/// Pairs with `floor_divide`, so the result has the sign of the right.
+pub fn modulo(left: i64, right: i64) -> Result<i64, ErrorKind> {
+    let (_, remainder) = div_rem(ExprOp::Mod, left, right)?;
+    if remainder != 0 && (remainder < 0) != (right < 0) {
+        Ok(remainder + right)
+    } else {
+        Ok(remainder)
+    }
 }
 
-pub fn divide(left: i64, right: i64) -> Result<i64, Error> {
+/// Divides, rounding towards zero, and gives what's left over. Rounding the
+/// other ways moves the quotient by one at most, so it can't overflow then.
+fn div_rem(op: ExprOp, left: i64, right: i64) -> Result<(i64, i64), ErrorKind> {
     if right == 0 {
-        return Err(Error::DivideByZero);
+        return Err(ErrorKind::DivideByZero);
     }
 
-    Ok(left / right)
+    let quotient = left
+        .checked_div(right)
+        .ok_or(ErrorKind::Overflow(op, left, right))?;
+    Ok((quotient, left % right))
 }
 
-pub fn power(left: i64, right: i64) -> Result<i64, Error> {
+pub fn power(left: i64, right: i64) -> Result<i64, ErrorKind> {
     if right < 0 {
-        return Err(Error::NegativePowerNotImplemented);
+        return Err(ErrorKind::NegativePowerNotImplemented);
     }
 
-    Ok(left.pow(right as u32))
+    let overflow = ErrorKind::Overflow(ExprOp::Pow, left, right);
+    match u32::try_from(right) {
+        Ok(exponent) => left.checked_pow(exponent).ok_or(overflow),
+
+        // Only 0, 1, and -1 can be raised this high without overflowing.
+        Err(_) => match left {
+            0 | 1 => Ok(left),
+            -1 if right % 2 == 0 => Ok(1),
+            -1 => Ok(-1),
+            _ => Err(overflow),
+        },
+    }
 }
 
-pub fn roll(rng: &mut impl Rng, num_die: i64, num_sides: i64) -> Result<i64, Error> {
-    let mut total = 0;
+pub fn sum(values: impl IntoIterator<Item = i64>) -> Result<i64, ErrorKind> {
+    values.into_iter().try_fold(0, add)
+}
 
-    for _ in 0..num_die {
-        total += roll_one(rng, num_sides);
+pub fn roll(
+    source: &mut impl DiceSource,
+    num_die: i64,
+    sides: &Sides,
+) -> Result<Vec<i64>, ErrorKind> {
+    (0..num_die).map(|_| source.roll_die(sides)).collect()
+}
+
+/// How many faces a die with the given sides has.
+pub fn num_faces(sides: &Sides) -> i64 {
+    match *sides {
+        Sides::Number(num_sides) => num_sides.saturating_abs(),
+        Sides::Fudge => 3,
+        Sides::Faces(ref faces) => faces.len() as i64,
+    }
+}
+
+/// Every face of a die with the given sides, each as likely as the others.
+pub fn faces(sides: &Sides) -> Vec<i64> {
+    match *sides {
+        Sides::Number(num_sides) if num_sides < 0 => (num_sides..=-1).collect(),
+        Sides::Number(num_sides) if num_sides > 0 => (1..=num_sides).collect(),
+        Sides::Fudge => vec![-1, 0, 1],
+        Sides::Faces(ref faces) if !faces.is_empty() => faces.clone(),
+        _ => vec![0],
     }
+}
 
-    Ok(total)
+/// The highest face of a die with the given sides.
+pub fn max_face(sides: &Sides) -> i64 {
+    match *sides {
+        Sides::Number(num_sides) if num_sides < 0 => -1,
+        Sides::Number(num_sides) => num_sides,
+        Sides::Fudge => 1,
+        Sides::Faces(ref faces) => faces.iter().copied().max().unwrap_or(0),
+    }
+}
+
+/// The lowest face of a die with the given sides.
+pub fn min_face(sides: &Sides) -> i64 {
+    match *sides {
+        Sides::Number(num_sides) if num_sides > 0 => 1,
+        Sides::Number(num_sides) => num_sides,
+        Sides::Fudge => -1,
+        Sides::Faces(ref faces) => faces.iter().copied().min().unwrap_or(0),
+    }
+}
+
+pub fn roll_one(rng: &mut impl Rng, sides: &Sides) -> i64 {
+    match *sides {
+        Sides::Number(num_sides) => roll_number(rng, num_sides),
+        Sides::Fudge => rng.gen_range(-1..=1),
+        Sides::Faces(ref faces) => roll_faces(rng, faces),
+    }
+}
+
+fn roll_faces(rng: &mut impl Rng, faces: &[i64]) -> i64 {
+    if faces.is_empty() {
+        0
+    } else {
+        faces[rng.gen_range(0..faces.len())]
+    }
 }
 
-fn roll_one(rng: &mut impl Rng, num_sides: i64) -> i64 {
+fn roll_number(rng: &mut impl Rng, num_sides: i64) -> i64 {
     if num_sides == 0 {
         0
     } else if num_sides < 0 {
-        (-rng.gen_range(0, -num_sides)) - 1
+        rng.gen_range(num_sides..=-1)
     } else {
-        rng.gen_range(0, num_sides) + 1
+        rng.gen_range(0..num_sides) + 1
     }
 }
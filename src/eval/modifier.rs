@@ -0,0 +1,208 @@
+use ::std::cmp::Ordering;
+
+use crate::ast::{Condition, RollModifier};
+use crate::eval::maths;
+use crate::eval::{DiceSource, Die, ErrorKind, Limit, Limits, Sides};
+
+pub fn apply(
+    source: &mut impl DiceSource,
+    limits: &Limits,
+    dice: &mut Vec<Die>,
+    sides: &Sides,
+    modifier: &RollModifier,
+) -> Result<(), ErrorKind> {
+    match *modifier {
+        RollModifier::KeepHighest(n) => keep(dice, n, |a, b| b.cmp(&a)),
+        RollModifier::KeepLowest(n) => keep(dice, n, |a, b| a.cmp(&b)),
+        RollModifier::DropHighest(n) => drop(dice, n, |a, b| b.cmp(&a)),
+        RollModifier::DropLowest(n) => drop(dice, n, |a, b| a.cmp(&b)),
+        RollModifier::Explode(ref condition) => {
+            let condition = condition_or_max(condition, sides);
+            explode(source, dice, sides, &condition, 0, limits.max_explosions)?
+        }
+        RollModifier::Compound(ref condition) => {
+            let condition = condition_or_max(condition, sides);
+            compound(source, dice, sides, &condition, limits.max_explosions)?
+        }
+        RollModifier::Penetrate(ref condition) => {
+            let condition = condition_or_max(condition, sides);
+            explode(source, dice, sides, &condition, 1, limits.max_explosions)?
+        }
+        RollModifier::Reroll(ref condition) => {
+            let condition = condition_or_min(condition, sides);
+            reroll(source, dice, sides, &condition, Some(limits.max_rerolls))?
+        }
+        RollModifier::RerollOnce(ref condition) => {
+            let condition = condition_or_min(condition, sides);
+            reroll(source, dice, sides, &condition, None)?
+        }
+        RollModifier::Success(ref condition) => {
+            for die in dice.iter_mut().filter(|die| is_match(condition, die.value)) {
+                die.is_success = true;
+            }
+        }
+        RollModifier::Failure(ref condition) => {
+            for die in dice.iter_mut().filter(|die| is_match(condition, die.value)) {
+                die.is_failure = true;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Keeps the first `n` dice, when sorted by `order`, and drops the rest.
+/// Dice which have already been dropped stay dropped.
+fn keep(dice: &mut [Die], n: i64, order: impl Fn(i64, i64) -> Ordering) {
+    for i in sorted_kept(dice, order).into_iter().skip(n as usize) {
+        dice[i].is_kept = false;
+    }
+}
+
+/// Drops the first `n` dice, when sorted by `order`, and keeps the rest.
+fn drop(dice: &mut [Die], n: i64, order: impl Fn(i64, i64) -> Ordering) {
+    for i in sorted_kept(dice, order).into_iter().take(n as usize) {
+        dice[i].is_kept = false;
+    }
+}
+
+/// Rolls an extra die for each kept die matching the condition, placing it
+/// straight after the die that exploded. Extra dice can explode too.
+///
+/// The `penalty` is taken off each extra die, after checking if it explodes.
+/// This is `1` for penetrating dice, and `0` otherwise.
+///
+/// Without `max_explosions` dice such as `1d1!` would never finish.
+fn explode(
+    source: &mut impl DiceSource,
+    dice: &mut Vec<Die>,
+    sides: &Sides,
+    condition: &Condition,
+    penalty: i64,
+    max_explosions: usize,
+) -> Result<(), ErrorKind> {
+    let mut exploded_dice = Vec::with_capacity(dice.len());
+
+    for mut die in dice.drain(..) {
+        let mut rolled = die.value;
+        let mut num_explosions = 0;
+
+        while die.is_kept && !die.is_exploded && is_match(condition, rolled) {
+            num_explosions += 1;
+            if num_explosions > max_explosions {
+                return Err(ErrorKind::LimitExceeded(Limit::Explosions(max_explosions)));
+            }
+
+            die.is_exploded = true;
+            exploded_dice.push(die);
+
+            rolled = source.roll_die(sides)?;
+            die = Die::new(maths::sub(rolled, penalty)?);
+        }
+
+        exploded_dice.push(die);
+    }
+
+    *dice = exploded_dice;
+    Ok(())
+}
+
+/// Like exploding, except the extra rolls are added onto the die which
+/// exploded, rather than becoming dice of their own.
+fn compound(
+    source: &mut impl DiceSource,
+    dice: &mut [Die],
+    sides: &Sides,
+    condition: &Condition,
+    max_explosions: usize,
+) -> Result<(), ErrorKind> {
+    for die in dice.iter_mut().filter(|die| die.is_kept) {
+        let mut rolled = die.value;
+        let mut num_explosions = 0;
+
+        while is_match(condition, rolled) {
+            num_explosions += 1;
+            if num_explosions > max_explosions {
+                return Err(ErrorKind::LimitExceeded(Limit::Explosions(max_explosions)));
+            }
+
+            rolled = source.roll_die(sides)?;
+            die.value = maths::add(die.value, rolled)?;
+            die.is_compounded = true;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces each kept die matching the condition with a new die, placing the
+/// new die straight after the one it replaced.
+///
+/// With `max_rerolls` dice are rerolled until they stop matching, and it's an
+/// error to still be matching after that many rerolls. Without this `1d6r<7`
+/// would never finish. With `None` dice are only rerolled once.
+fn reroll(
+    source: &mut impl DiceSource,
+    dice: &mut Vec<Die>,
+    sides: &Sides,
+    condition: &Condition,
+    max_rerolls: Option<usize>,
+) -> Result<(), ErrorKind> {
+    let mut rerolled_dice = Vec::with_capacity(dice.len());
+
+    for mut die in dice.drain(..) {
+        let mut num_rerolls = 0;
+
+        while die.is_kept && is_match(condition, die.value) {
+            match max_rerolls {
+                Some(max_rerolls) if num_rerolls == max_rerolls => {
+                    return Err(ErrorKind::LimitExceeded(Limit::Rerolls(max_rerolls)));
+                }
+                None if num_rerolls == 1 => break,
+                _ => num_rerolls += 1,
+            }
+
+            die.is_kept = false;
+            die.is_rerolled = true;
+            rerolled_dice.push(die);
+            die = Die::new(source.roll_die(sides)?);
+        }
+
+        rerolled_dice.push(die);
+    }
+
+    *dice = rerolled_dice;
+    Ok(())
+}
+
+/// Modifiers with no condition match on the highest face of the die.
+pub fn condition_or_max(condition: &Option<Condition>, sides: &Sides) -> Condition {
+    condition
+        .clone()
+        .unwrap_or_else(|| Condition::Equal(maths::max_face(sides)))
+}
+
+/// Modifiers with no condition match on the lowest face of the die.
+pub fn condition_or_min(condition: &Option<Condition>, sides: &Sides) -> Condition {
+    condition
+        .clone()
+        .unwrap_or_else(|| Condition::Equal(maths::min_face(sides)))
+}
+
+pub fn is_match(condition: &Condition, value: i64) -> bool {
+    match *condition {
+        Condition::Equal(n) => value == n,
+        Condition::AtLeast(n) => value >= n,
+        Condition::AtMost(n) => value <= n,
+    }
+}
+
+/// Returns the indexes of the dice still kept, sorted by their value.
+fn sorted_kept(dice: &[Die], order: impl Fn(i64, i64) -> Ordering) -> Vec<usize> {
+    let mut indexes = (0..dice.len())
+        .filter(|&i| dice[i].is_kept)
+        .collect::<Vec<usize>>();
+    indexes.sort_by(|&a, &b| order(dice[a].value, dice[b].value));
+
+    indexes
+}
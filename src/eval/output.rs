@@ -1,15 +1,237 @@
+use crate::ast::{ExprOp, RollModifier, UnaryOp};
+use crate::eval::maths;
+use crate::eval::{ErrorKind, Estimate};
 
-use ::std::fmt::{Display, Result, Formatter};
+/// What an expression came out as, and how it was worked out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+    /// What the expression came out as.
+    pub value: Value,
+    pub(crate) breakdown: Breakdown,
+}
+
+impl Output {
+    /// Every roll made, in the order they were rolled.
+    pub fn rolls(&self) -> Vec<&Roll> {
+        let mut rolls = Vec::new();
+        self.breakdown.collect_rolls(&mut rolls);
+
+        rolls
+    }
+}
+
+/// How a value was worked out, following the shape of the expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Breakdown {
+    Integer(i64),
+    Operator(ExprOp, Box<Breakdown>, Box<Breakdown>),
+    Unary(UnaryOp, Box<Breakdown>),
+
+    /// A call to a function by name, along with how its arguments were worked out.
+    Call(String, Vec<Breakdown>),
+
+    /// A roll, along with how its number of dice and sides were worked out.
+    Roll(Box<Breakdown>, Box<Breakdown>, Roll),
+
+    /// Sides which are not a number, such as the `F` in `4dF`.
+    Sides(Sides),
+
+    /// A chance, which is worked out rather than rolled.
+    Chance(Chance),
+}
+
+impl Breakdown {
+    fn collect_rolls<'a>(&'a self, rolls: &mut Vec<&'a Roll>) {
+        match self {
+            Self::Integer(_) | Self::Sides(_) | Self::Chance(_) => {}
+            Self::Operator(_, left, right) => {
+                left.collect_rolls(rolls);
+                right.collect_rolls(rolls);
+            }
+            Self::Unary(_, expr) => expr.collect_rolls(rolls),
+            Self::Call(_, args) => {
+                for arg in args {
+                    arg.collect_rolls(rolls);
+                }
+            }
+            Self::Roll(num_dice, sides, roll) => {
+                num_dice.collect_rolls(rolls);
+                sides.collect_rolls(rolls);
+                rolls.push(roll);
+            }
+        }
+    }
+}
 
+/// What an expression came out as. Values are kept apart by what they count,
+/// so they can be written as, i.e. `3 successes` rather than `3`.
 #[derive(Clone, Debug, PartialEq)]
-pub enum Output {
-  Integer(i64)
+pub enum Value {
+    /// A total, such as from adding up dice.
+    Integer(i64),
+
+    /// How many dice succeeded, less those which failed, i.e. from `5d10>=7`.
+    Successes(i64),
+
+    /// A total on the Fate ladder, from rolling Fudge dice.
+    Fate(i64),
+
+    /// The chance of a condition being true.
+    Chance(Chance),
+}
+
+impl Value {
+    /// Chances are only worked out on their own, so they have no whole
+    /// number to be used within another expression.
+    pub fn to_i64(&self) -> Result<i64, ErrorKind> {
+        match *self {
+            Self::Integer(n) => Ok(n),
+            Self::Successes(n) => Ok(n),
+            Self::Fate(n) => Ok(n),
+            Self::Chance(_) => Err(ErrorKind::NestedChance),
+        }
+    }
+
+    /// Whether this counts successes, rather than being a total.
+    pub fn is_successes(&self) -> bool {
+        matches!(self, Self::Successes(_))
+    }
+
+    /// Whether this is on the Fate ladder.
+    pub fn is_fate(&self) -> bool {
+        matches!(self, Self::Fate(_))
+    }
+}
+
+/// The chance of a condition being true, from `0.0` to `1.0`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Chance {
+    /// Worked out exactly, from every way the dice could land.
+    Exact(f64),
+
+    /// Estimated by rolling the condition many times, along with how many.
+    Estimated(Estimate, usize),
+}
+
+impl Chance {
+    /// The chance, whether it was worked out exactly or estimated.
+    pub fn probability(&self) -> f64 {
+        match *self {
+            Self::Exact(probability) => probability,
+            Self::Estimated(estimate, _) => estimate.probability,
+        }
+    }
+}
+
+/// A single group of dice, such as the `4d6kh3` in `4d6kh3 + 2`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Roll {
+    /// How many dice were rolled, before any were exploded or rerolled.
+    pub num_dice: i64,
+
+    /// The sides of the dice.
+    pub sides: Sides,
+
+    /// What was done to the dice after they were rolled, in order.
+    pub modifiers: Vec<RollModifier>,
+
+    pub(crate) dice: Vec<Die>,
+}
+
+impl Roll {
+    /// The face of each die which was kept, in the order they were rolled.
+    pub fn values(&self) -> Vec<i64> {
+        self.dice
+            .iter()
+            .filter(|die| die.is_kept)
+            .map(|die| die.value)
+            .collect()
+    }
+
+    /// What the roll came out as, which counts successes if any were looked
+    /// for, and totals the dice otherwise.
+    pub fn value(&self) -> Result<Value, ErrorKind> {
+        if self.is_counting_successes() {
+            Ok(Value::Successes(self.num_successes()))
+        } else if self.sides == Sides::Fudge {
+            Ok(Value::Fate(self.total()?))
+        } else {
+            Ok(Value::Integer(self.total()?))
+        }
+    }
+
+    /// The sum of the dice which were kept.
+    pub fn total(&self) -> Result<i64, ErrorKind> {
+        maths::sum(
+            self.dice
+                .iter()
+                .filter(|die| die.is_kept)
+                .map(|die| die.value),
+        )
+    }
+
+    /// The number of kept dice which succeeded, minus those which failed.
+    pub fn num_successes(&self) -> i64 {
+        self.dice
+            .iter()
+            .filter(|die| die.is_kept)
+            .map(|die| die.is_success as i64 - die.is_failure as i64)
+            .sum()
+    }
+
+    /// Whether any dice are counted as successes or failures.
+    pub fn is_counting_successes(&self) -> bool {
+        self.modifiers.iter().any(|modifier| {
+            matches!(
+                modifier,
+                RollModifier::Success(_) | RollModifier::Failure(_)
+            )
+        })
+    }
+}
+
+/// The sides of the dice in a roll.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sides {
+    /// Dice numbered from 1 up to this.
+    Number(i64),
+
+    /// Fudge dice, with the faces -1, 0, and +1.
+    Fudge,
+
+    /// Dice with a custom list of faces, each as likely as the others.
+    Faces(Vec<i64>),
+}
+
+/// A single die rolled, and what happened to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Die {
+    pub value: i64,
+    pub is_kept: bool,
+
+    /// When true, the die after this one was rolled because this one exploded.
+    pub is_exploded: bool,
+
+    /// When true, this die exploded and the extra rolls were added to its value.
+    pub is_compounded: bool,
+
+    /// When true, the die after this one was rolled to replace it.
+    pub is_rerolled: bool,
+
+    pub is_success: bool,
+    pub is_failure: bool,
 }
 
-impl Display for Output {
-  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-    match self {
-      Self::Integer(n) => write!(f, "{}", n),
+impl Die {
+    pub fn new(value: i64) -> Self {
+        Self {
+            value,
+            is_kept: true,
+            is_exploded: false,
+            is_compounded: false,
+            is_rerolled: false,
+            is_success: false,
+            is_failure: false,
+        }
     }
-  }
 }
@@ -0,0 +1,103 @@
+use crate::distribution::Stats;
+
+use ::std::collections::BTreeMap;
+
+/// How many standard deviations either side of an estimate its confidence
+/// interval spans. This gives a 95% confidence interval.
+const Z_SCORE: f64 = 1.96;
+
+/// How many times each value came up, from rolling an expression many times.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Simulation {
+    counts: BTreeMap<i64, usize>,
+    num_samples: usize,
+
+    /// The lowest and highest values which could have come up, if known,
+    /// as rare values may never have been rolled.
+    bounds: Option<(i64, i64)>,
+}
+
+impl Simulation {
+    pub fn new(
+        counts: BTreeMap<i64, usize>,
+        num_samples: usize,
+        bounds: Option<(i64, i64)>,
+    ) -> Self {
+        Self {
+            counts,
+            num_samples,
+            bounds,
+        }
+    }
+
+    pub fn num_samples(&self) -> usize {
+        self.num_samples
+    }
+
+    /// Each value rolled, from lowest to highest, and its estimated probability.
+    pub fn estimates(&self) -> Vec<(i64, Estimate)> {
+        self.counts
+            .iter()
+            .map(|(&value, &count)| (value, Estimate::new(count, self.num_samples)))
+            .collect()
+    }
+
+    /// How likely a value other than zero is, which is whether a comparison,
+    /// such as `1d20 >= 15`, is true.
+    pub fn chance(&self) -> Estimate {
+        let count = self
+            .counts
+            .iter()
+            .filter(|&(&value, _)| value != 0)
+            .map(|(_, &count)| count)
+            .sum();
+
+        Estimate::new(count, self.num_samples)
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats::from_counts(&self.counts, self.num_samples, self.bounds)
+    }
+}
+
+/// An estimated probability, along with a 95% confidence interval around it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    /// How often it came up, out of every roll.
+    pub probability: f64,
+
+    /// The lowest the probability is likely to be.
+    pub low: f64,
+
+    /// The highest the probability is likely to be.
+    pub high: f64,
+}
+
+impl Estimate {
+    /// Uses the Wilson score interval, which stays within 0 and 1,
+    /// even for values which came up rarely or not at all.
+    fn new(count: usize, num_samples: usize) -> Self {
+        if num_samples == 0 {
+            return Self {
+                probability: 0.0,
+                low: 0.0,
+                high: 1.0,
+            };
+        }
+
+        let n = num_samples as f64;
+        let probability = count as f64 / n;
+        let z_squared = Z_SCORE * Z_SCORE;
+
+        let divisor = 1.0 + z_squared / n;
+        let centre = (probability + z_squared / (2.0 * n)) / divisor;
+        let spread = probability * (1.0 - probability) / n + z_squared / (4.0 * n * n);
+        let margin = Z_SCORE * spread.sqrt() / divisor;
+
+        Self {
+            probability,
+            low: (centre - margin).max(0.0),
+            high: (centre + margin).min(1.0),
+        }
+    }
+}
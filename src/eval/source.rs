@@ -0,0 +1,51 @@
+use crate::eval::maths;
+use crate::eval::{ErrorKind, Sides};
+
+use ::rand::RngCore;
+use ::std::collections::VecDeque;
+
+/// Where the faces of the dice rolled come from.
+///
+/// Any random number generator is a source, such as `OsRng` for rolls which
+/// mustn't be predicted. Other sources can give faces from elsewhere, such as
+/// a game which was recorded and is being replayed.
+pub trait DiceSource {
+    /// Rolls a single die, giving the face it landed on, or an error if
+    /// there are no more faces to give.
+    fn roll_die(&mut self, sides: &Sides) -> Result<i64, ErrorKind>;
+}
+
+impl<R: RngCore> DiceSource for R {
+    fn roll_die(&mut self, sides: &Sides) -> Result<i64, ErrorKind> {
+        Ok(maths::roll_one(self, sides))
+    }
+}
+
+/// Gives each face from a list, in order, whatever the sides of the die,
+/// so the dice rolled are known ahead of time.
+///
+/// Rolling more dice than there are faces left is an error.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scripted {
+    faces: VecDeque<i64>,
+}
+
+impl Scripted {
+    /// Gives the faces in the order they're listed.
+    pub fn new(faces: impl IntoIterator<Item = i64>) -> Self {
+        Self {
+            faces: faces.into_iter().collect(),
+        }
+    }
+
+    /// How many faces are left to be rolled.
+    pub fn remaining(&self) -> usize {
+        self.faces.len()
+    }
+}
+
+impl DiceSource for Scripted {
+    fn roll_die(&mut self, _sides: &Sides) -> Result<i64, ErrorKind> {
+        self.faces.pop_front().ok_or(ErrorKind::OutOfFaces)
+    }
+}
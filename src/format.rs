@@ -1,9 +1,17 @@
+use crate::ast;
+use crate::distribution;
 use crate::eval;
-use ::pom;
+use crate::parse;
+use ::std::collections::{BTreeMap, BTreeSet};
 use ::std::fmt;
 use ::std::io;
 
-pub type ResultOutput = Result<Vec<Result<eval::Output, eval::Error>>, pom::Error>;
+pub type ResultOutput = Result<Vec<Result<eval::Output, eval::Error>>, parse::Error>;
+pub type ResultOdds = Result<Vec<Result<distribution::Odds, eval::Error>>, parse::Error>;
+pub type ResultHistogram =
+    Result<Vec<Result<(eval::Output, distribution::Odds), eval::Error>>, parse::Error>;
+pub type ResultStats = Result<Vec<Result<distribution::Stats, eval::Error>>, parse::Error>;
+pub type ResultVersus = Result<Result<distribution::Versus, eval::Error>, parse::Error>;
 
 pub fn fmt(
     f: &mut impl io::Write,
@@ -16,18 +24,166 @@ pub fn fmt(
         "{} rolled ... {}\nand got ... {}",
         username,
         input,
-        ResultOutputFormatter(&output)
+        ResultOutputFormatter(output, input)
     )?;
 
     Ok(())
 }
 
-struct ResultOutputFormatter<'a>(&'a ResultOutput);
+pub fn fmt_odds(
+    f: &mut impl io::Write,
+    username: &str,
+    input: &str,
+    output: &ResultOdds,
+) -> io::Result<()> {
+    writeln!(
+        f,
+        "{} asked the odds of ... {}\nand got ...{}",
+        username,
+        input,
+        ResultLinesFormatter(output, input)
+    )?;
+
+    Ok(())
+}
+
+pub fn fmt_stats(
+    f: &mut impl io::Write,
+    username: &str,
+    input: &str,
+    output: &ResultStats,
+) -> io::Result<()> {
+    writeln!(
+        f,
+        "{} asked the stats of ... {}\nand got ...{}",
+        username,
+        input,
+        ResultLinesFormatter(output, input)
+    )?;
+
+    Ok(())
+}
+
+/// How far the longest bar of a histogram stretches.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Where the text is being sent, and so how it should be marked up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Markup {
+    /// Plain text, such as for the command line.
+    Plain,
+
+    /// Charts are placed in code blocks, so Slack keeps them lined up.
+    Slack,
+}
+
+pub fn fmt_histogram(
+    f: &mut impl io::Write,
+    username: &str,
+    input: &str,
+    output: &ResultHistogram,
+    markup: Markup,
+) -> io::Result<()> {
+    write!(f, "{} rolled ... {}\nand got ...", username, input)?;
+
+    match output {
+        Ok(results) => {
+            for result in results {
+                match result {
+                    Ok((rolled, odds)) => {
+                        // A chance is worked out rather than rolled, so has no value to mark.
+                        let rolled_value = match rolled.value {
+                            eval::Value::Chance(_) => None,
+                            ref value => value.to_i64().ok(),
+                        };
+                        let histogram = Histogram::new(odds, rolled_value);
+                        writeln!(f)?;
+                        writeln!(f, "{}", rolled)?;
+                        fmt_chart(f, &histogram, markup)?;
+                    }
+                    Err(err) => write!(f, "\n{}", ErrorFormatter(err, input))?,
+                }
+            }
+        }
+        Err(parse_err) => write!(f, " error ... {}", parse_err)?,
+    }
+
+    writeln!(f)
+}
+
+/// Writes how each expression is summarised, how often each beats the other,
+/// and a chart of both side by side.
+pub fn fmt_versus(
+    f: &mut impl io::Write,
+    username: &str,
+    left: &str,
+    right: &str,
+    output: &ResultVersus,
+    markup: Markup,
+) -> io::Result<()> {
+    let (left, right) = (left.trim(), right.trim());
+    write!(
+        f,
+        "{} compared ... {} vs {}\nand got ...",
+        username, left, right
+    )?;
+
+    match output {
+        Ok(Ok(versus)) => {
+            let (left_stats, right_stats) = (versus.left.stats(), versus.right.stats());
+            let width = left.len().max(right.len());
+            writeln!(f)?;
+            writeln!(
+                f,
+                "{:<width$}  mean {:.2}, variance {:.2}",
+                left,
+                left_stats.mean,
+                left_stats.variance(),
+                width = width
+            )?;
+            writeln!(
+                f,
+                "{:<width$}  mean {:.2}, variance {:.2}",
+                right,
+                right_stats.mean,
+                right_stats.variance(),
+                width = width
+            )?;
+            writeln!(
+                f,
+                "{} beats {} {}, {} beats {} {}, tie {}",
+                left,
+                right,
+                Percentage(versus.left_wins),
+                right,
+                left,
+                Percentage(versus.right_wins),
+                Percentage(versus.draw)
+            )?;
+
+            let chart = VersusChart::new(versus, left, right);
+            fmt_chart(f, &chart, markup)?;
+        }
+        Ok(Err(err)) => write!(f, " {}", err)?,
+        Err(parse_err) => write!(f, " error ... {}", parse_err)?,
+    }
+
+    writeln!(f)
+}
+
+fn fmt_chart(f: &mut impl io::Write, chart: &impl fmt::Display, markup: Markup) -> io::Result<()> {
+    match markup {
+        Markup::Plain => write!(f, "{}", chart),
+        Markup::Slack => write!(f, "```\n{}\n```", chart),
+    }
+}
+
+struct ResultOutputFormatter<'a>(&'a ResultOutput, &'a str);
 impl<'a> fmt::Display for ResultOutputFormatter<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ResultOutputFormatter(Ok(outputs)) => fmt_parse_ok(f, &outputs),
-            ResultOutputFormatter(Err(parse_err)) => fmt_parse_err(f, &parse_err),
+            ResultOutputFormatter(Ok(outputs), input) => fmt_parse_ok(f, outputs, input),
+            ResultOutputFormatter(Err(parse_err), _) => fmt_parse_err(f, parse_err),
         }
     }
 }
@@ -35,29 +191,90 @@ impl<'a> fmt::Display for ResultOutputFormatter<'a> {
 fn fmt_parse_ok(
     f: &mut fmt::Formatter<'_>,
     outputs: &Vec<Result<eval::Output, eval::Error>>,
+    input: &str,
 ) -> fmt::Result {
     for (i, output) in outputs.iter().enumerate() {
         if i > 0 {
-            write!(f, " ")?;
+            write!(f, ", ")?;
         }
 
-        fmt_output(f, output)?;
+        fmt_output(f, output, input)?;
     }
 
     Ok(())
 }
 
-fn fmt_parse_err(f: &mut fmt::Formatter<'_>, err: &pom::Error) -> fmt::Result {
-    write!(f, "error ... {:?}", err)
+/// Writes each result on a new line, as they can span several lines themselves.
+struct ResultLinesFormatter<'a, T>(
+    &'a Result<Vec<Result<T, eval::Error>>, parse::Error>,
+    &'a str,
+);
+impl<'a, T: fmt::Display> fmt::Display for ResultLinesFormatter<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResultLinesFormatter(Ok(results), input) => {
+                for result in results.iter() {
+                    match result {
+                        Ok(item) => write!(f, "\n{}", item)?,
+                        Err(err) => write!(f, "\n{}", ErrorFormatter(err, input))?,
+                    }
+                }
+
+                Ok(())
+            }
+            ResultLinesFormatter(Err(parse_err), _) => {
+                write!(f, " ")?;
+                fmt_parse_err(f, parse_err)
+            }
+        }
+    }
+}
+
+fn fmt_parse_err(f: &mut fmt::Formatter<'_>, err: &parse::Error) -> fmt::Result {
+    write!(f, "error ... {}", err)
+}
+
+/// Writes what went wrong, followed by the input with a caret under where,
+/// and then a similar input which does parse, if there is one.
+impl fmt::Display for parse::Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some((last, rest)) = self.expected.split_last() {
+            write!(f, ", expected ")?;
+            if !rest.is_empty() {
+                fmt_list(f, rest, ", ")?;
+                write!(f, " or ")?;
+            }
+            write!(f, "{}", last)?;
+        }
+
+        // The caret is placed by characters rather than bytes, so it lines up.
+        let indent = self.input[..self.span.start].chars().count();
+        let width = self.span.text(&self.input).chars().count().max(1);
+        write!(
+            f,
+            "\n{}\n{}{}",
+            self.input,
+            " ".repeat(indent),
+            "^".repeat(width)
+        )?;
+
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\ndid you mean `{}`?", suggestion)?;
+        }
+
+        Ok(())
+    }
 }
 
 fn fmt_output(
     f: &mut fmt::Formatter<'_>,
     output_result: &Result<eval::Output, eval::Error>,
+    input: &str,
 ) -> fmt::Result {
     match output_result {
         Ok(output) => fmt_output_ok(f, output),
-        Err(err) => fmt_output_err(f, err),
+        Err(err) => fmt_output_err(f, err, input),
     }
 }
 
@@ -65,23 +282,796 @@ fn fmt_output_ok(f: &mut fmt::Formatter<'_>, output: &eval::Output) -> fmt::Resu
     write!(f, "{}", output)
 }
 
-fn fmt_output_err(f: &mut fmt::Formatter<'_>, err: &eval::Error) -> fmt::Result {
-    write!(f, "{}", err)
+fn fmt_output_err(f: &mut fmt::Formatter<'_>, err: &eval::Error, input: &str) -> fmt::Result {
+    write!(f, "{}", ErrorFormatter(err, input))
 }
 
+/// Writes an error along with the part of the input to blame, such as the
+/// `2 / 0` in `1d6 + 2 / 0`, unless that's the whole input.
+struct ErrorFormatter<'a>(&'a eval::Error, &'a str);
+impl<'a> fmt::Display for ErrorFormatter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ErrorFormatter(err, input) = self;
+        write!(f, "{}", err)?;
+
+        let blamed = err.span.map_or("", |span| span.text(input).trim());
+        if !blamed.is_empty() && blamed != input.trim() {
+            write!(f, " in `{}`", blamed)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Outputs with dice are written with how they were rolled,
+/// i.e. `2d6 [3, 5] + 4 = 12`. Those without are written as just the value.
 impl fmt::Display for eval::Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.rolls().is_empty() {
+            write!(f, "{}", self.value)
+        } else {
+            write!(f, "{} = {}", self.breakdown, self.value)
+        }
+    }
+}
+
+impl fmt::Display for eval::Breakdown {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Integer(n) => write!(f, "{}", n),
+            Self::Sides(sides) => write!(f, "{}", sides),
+            Self::Chance(chance) => write!(f, "{}", chance),
+            Self::Operator(op, left, right) => {
+                let is_left_bracketed = is_bracketed(op, left, false);
+                let is_right_bracketed = is_bracketed(op, right, true);
+
+                fmt_bracketed(f, left, is_left_bracketed)?;
+                write!(f, " {} ", op)?;
+                fmt_bracketed(f, right, is_right_bracketed)
+            }
+            Self::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                fmt_list(f, args, ", ")?;
+                write!(f, ")")
+            }
+            Self::Unary(op, expr) => {
+                let is_expr_bracketed = match &**expr {
+                    Self::Operator(expr_op, _, _) => precedence(expr_op) < UNARY_PRECEDENCE,
+                    _ => false,
+                };
+
+                write!(f, "{}", op)?;
+                fmt_bracketed(f, expr, is_expr_bracketed)
+            }
+            Self::Roll(num_dice, sides, roll) => {
+                fmt_bracketed(f, num_dice, !is_leaf(num_dice))?;
+                write!(f, "d")?;
+                fmt_bracketed(f, sides, !is_leaf(sides))?;
+                fmt_dice(f, roll)
+            }
+        }
+    }
+}
+
+/// Brackets are needed when the child would otherwise be worked out after
+/// its parent, i.e. the `1 + 2` in `(1 + 2) * 3`.
+fn is_bracketed(parent_op: &ast::ExprOp, child: &eval::Breakdown, is_right: bool) -> bool {
+    match child {
+        eval::Breakdown::Operator(child_op, _, _) => {
+            let parent_precedence = precedence(parent_op);
+            let child_precedence = precedence(child_op);
+
+            if child_precedence == parent_precedence {
+                // `^` groups to the right, and the others to the left.
+                (*parent_op == ast::ExprOp::Pow) != is_right
+            } else {
+                child_precedence < parent_precedence
+            }
+        }
+        // `-2 ^ 2` is `-(2 ^ 2)`, so `(-2) ^ 2` needs its brackets.
+        eval::Breakdown::Unary(_, _) => precedence(parent_op) > UNARY_PRECEDENCE,
+        _ => false,
+    }
+}
+
+/// `-` and `+` in front of an expression come between `*` and `^`.
+const UNARY_PRECEDENCE: u8 = 3;
+
+fn precedence(op: &ast::ExprOp) -> u8 {
+    match op {
+        ast::ExprOp::Compare(_) => 0,
+        ast::ExprOp::Add | ast::ExprOp::Sub => 1,
+        ast::ExprOp::Mult
+        | ast::ExprOp::Div
+        | ast::ExprOp::FloorDiv
+        | ast::ExprOp::CeilDiv
+        | ast::ExprOp::RoundDiv
+        | ast::ExprOp::Mod => 2,
+        ast::ExprOp::Pow => 4,
+        ast::ExprOp::Roll(_) => 5,
+    }
+}
+
+fn is_leaf(breakdown: &eval::Breakdown) -> bool {
+    matches!(
+        breakdown,
+        eval::Breakdown::Integer(_) | eval::Breakdown::Sides(_) | eval::Breakdown::Call(_, _)
+    )
+}
+
+fn fmt_bracketed(
+    f: &mut fmt::Formatter<'_>,
+    breakdown: &eval::Breakdown,
+    is_bracketed: bool,
+) -> fmt::Result {
+    if is_bracketed {
+        write!(f, "({})", breakdown)
+    } else {
+        write!(f, "{}", breakdown)
+    }
+}
+
+impl fmt::Display for ast::ExprOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Add => write!(f, "+"),
+            Self::Sub => write!(f, "-"),
+            Self::Mult => write!(f, "*"),
+            Self::Div => write!(f, "/"),
+            Self::FloorDiv => write!(f, "//"),
+            Self::CeilDiv => write!(f, "/^"),
+            Self::RoundDiv => write!(f, "/~"),
+            Self::Mod => write!(f, "%"),
+            Self::Pow => write!(f, "^"),
+            Self::Roll(_) => write!(f, "d"),
+            Self::Compare(comparison) => write!(f, "{}", comparison),
+        }
+    }
+}
+
+impl fmt::Display for ast::UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Neg => write!(f, "-"),
+            Self::Pos => write!(f, "+"),
+        }
+    }
+}
+
+impl fmt::Display for ast::Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Equal => write!(f, "=="),
+            Self::NotEqual => write!(f, "!="),
+            Self::Less => write!(f, "<"),
+            Self::LessOrEqual => write!(f, "<="),
+            Self::Greater => write!(f, ">"),
+            Self::GreaterOrEqual => write!(f, ">="),
+        }
+    }
+}
+
+impl fmt::Display for eval::Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Integer(n) => write!(f, "{}", n),
+            Self::Successes(1) => write!(f, "1 success"),
+            Self::Successes(n) => write!(f, "{} successes", n),
+            Self::Fate(n) => write!(f, "{} ({:+})", fate_ladder(*n), n),
+            Self::Chance(chance) => write!(f, "{}", chance),
+        }
+    }
+}
+
+/// Estimated chances are written with the range they're likely to be within,
+/// i.e. `49.50% (48.52% to 50.48%, estimated from 10000 rolls)`.
+impl fmt::Display for eval::Chance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(probability) => write!(f, "{}", Percentage(*probability)),
+            Self::Estimated(estimate, num_samples) => write!(
+                f,
+                "{} ({} to {}, estimated from {} rolls)",
+                Percentage(estimate.probability),
+                Percentage(estimate.low),
+                Percentage(estimate.high),
+                num_samples
+            ),
+        }
+    }
+}
+
+fn fate_ladder(n: i64) -> &'static str {
+    match n {
+        i64::MIN..=-4 => "Horrifying",
+        -3 => "Catastrophic",
+        -2 => "Terrible",
+        -1 => "Poor",
+        0 => "Mediocre",
+        1 => "Average",
+        2 => "Fair",
+        3 => "Good",
+        4 => "Great",
+        5 => "Superb",
+        6 => "Fantastic",
+        7 => "Epic",
+        8..=i64::MAX => "Legendary",
+    }
+}
+
+/// Writes a roll as it was written, followed by each of the dice rolled,
+/// i.e. `4d6kh3 [6, 5, 3, ~1~]`.
+impl fmt::Display for eval::Roll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d{}", self.num_dice, self.sides)?;
+        fmt_dice(f, self)
+    }
+}
+
+/// Writes the modifiers of a roll, followed by each of the dice rolled.
+/// Dropped dice are struck through.
+fn fmt_dice(f: &mut fmt::Formatter<'_>, roll: &eval::Roll) -> fmt::Result {
+    for modifier in &roll.modifiers {
+        write!(f, "{}", modifier)?;
+    }
+
+    write!(f, " [")?;
+    if roll.sides == eval::Sides::Fudge {
+        let fudge_dice = roll.dice.iter().map(FudgeDie).collect::<Vec<FudgeDie>>();
+        fmt_list(f, &fudge_dice, ", ")?;
+    } else {
+        fmt_list(f, &roll.dice, ", ")?;
+    }
+    write!(f, "]")
+}
+
+impl fmt::Display for eval::Sides {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "{}", n),
+            Self::Fudge => write!(f, "F"),
+            Self::Faces(faces) => {
+                write!(f, "{{")?;
+                fmt_list(f, faces, ",")?;
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for eval::Die {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_die(f, self, self.value)
+    }
+}
+
+/// Fudge dice are written as `+`, `−`, or a blank, rather than as numbers.
+struct FudgeDie<'a>(&'a eval::Die);
+impl<'a> fmt::Display for FudgeDie<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let FudgeDie(die) = self;
+        let face = match die.value.signum() {
+            1 => "+",
+            -1 => "−",
+            _ => " ",
+        };
+
+        fmt_die(f, die, face)
+    }
+}
+
+/// Dice which exploded are marked with a `!`, and are followed by the die
+/// they exploded into. Compounded dice are marked with `!!`. Rerolled dice
+/// are marked with an `r`, and are followed by the die that replaced them.
+/// Successes are marked with a `*`, and failures with an `f`.
+fn fmt_die(f: &mut fmt::Formatter<'_>, die: &eval::Die, face: impl fmt::Display) -> fmt::Result {
+    let strikethrough = if die.is_kept { "" } else { "~" };
+
+    write!(f, "{}{}", strikethrough, face)?;
+    if die.is_compounded {
+        write!(f, "!!")?;
+    } else if die.is_exploded {
+        write!(f, "!")?;
+    }
+    if die.is_rerolled {
+        write!(f, "r")?;
+    }
+    if die.is_success {
+        write!(f, "*")?;
+    }
+    if die.is_failure {
+        write!(f, "f")?;
+    }
+
+    write!(f, "{}", strikethrough)
+}
+
+impl fmt::Display for ast::RollModifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KeepHighest(n) => write!(f, "kh{}", n),
+            Self::KeepLowest(n) => write!(f, "kl{}", n),
+            Self::DropHighest(n) => write!(f, "dh{}", n),
+            Self::DropLowest(n) => write!(f, "dl{}", n),
+            Self::Explode(None) => write!(f, "!"),
+            Self::Explode(Some(condition)) => write!(f, "!{}", condition),
+            Self::Compound(None) => write!(f, "!!"),
+            Self::Compound(Some(condition)) => write!(f, "!!{}", condition),
+            Self::Penetrate(None) => write!(f, "!p"),
+            Self::Penetrate(Some(condition)) => write!(f, "!p{}", condition),
+            Self::Reroll(None) => write!(f, "r"),
+            Self::Reroll(Some(condition)) => write!(f, "r{}", condition),
+            Self::RerollOnce(None) => write!(f, "ro"),
+            Self::RerollOnce(Some(condition)) => write!(f, "ro{}", condition),
+            Self::Success(ast::Condition::Equal(n)) => write!(f, "={}", n),
+            Self::Success(condition) => write!(f, "{}", condition),
+            Self::Failure(condition) => write!(f, "f{}", condition),
         }
     }
 }
 
+impl fmt::Display for ast::Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Equal(n) => write!(f, "{}", n),
+            Self::AtLeast(n) => write!(f, ">={}", n),
+            Self::AtMost(n) => write!(f, "<={}", n),
+        }
+    }
+}
+
+fn fmt_list(
+    f: &mut fmt::Formatter<'_>,
+    items: &[impl fmt::Display],
+    separator: &str,
+) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, "{}", separator)?;
+        }
+
+        write!(f, "{}", item)?;
+    }
+
+    Ok(())
+}
+
 impl fmt::Display for eval::Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl fmt::Display for eval::ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::DivideByZero => write!(f, "divide by zero"),
             Self::NegativePowerNotImplemented => write!(f, "negative powers are not implemented"),
+            Self::Overflow(op, left, right) => {
+                write!(f, "{} {} {} is too big to work out", left, op, right)
+            }
+            Self::LimitExceeded(limit) => write!(f, "{}", limit),
+            Self::NestedChance => write!(f, "a chance can't be used within another expression"),
+            Self::OutOfFaces => write!(f, "ran out of scripted faces to roll"),
+            Self::UnknownFunction(name) => {
+                let names = eval::FUNCTIONS
+                    .iter()
+                    .map(|function| function.name)
+                    .collect::<Vec<&str>>();
+
+                write!(f, "there's no function called `{}`, try ", name)?;
+                fmt_list(f, &names, ", ")
+            }
+            Self::WrongArity(name, arity, num_args) => {
+                write!(f, "`{}` takes {}, but was given {}", name, arity, num_args)
+            }
+        }
+    }
+}
+
+impl fmt::Display for eval::Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (at_least, n) = match *self {
+            Self::Exactly(n) => ("", n),
+            Self::AtLeast(n) => ("at least ", n),
+        };
+        let plural = if n == 1 { "" } else { "s" };
+
+        write!(f, "{}{} argument{}", at_least, n, plural)
+    }
+}
+
+impl fmt::Display for eval::Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dice(n) => write!(f, "too many dice, at most {} can be rolled", n),
+            Self::Sides(n) => write!(f, "too many sides, dice can have at most {}", n),
+            Self::Explosions(n) => write!(f, "too many dice exploded, at most {} in a row", n),
+            Self::Rerolls(n) => write!(f, "too many dice rerolled, at most {} times", n),
+            Self::Depth(n) => write!(f, "too deeply nested, at most {} deep", n),
+            Self::Combinations(n) => write!(f, "too many combinations, at most {}", n),
+            Self::SimulatedDice(n) => write!(
+                f,
+                "too many dice to estimate the odds, at most {} can be rolled in all",
+                n
+            ),
+        }
+    }
+}
+
+impl fmt::Display for distribution::Odds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(distribution) => write!(f, "{}", distribution),
+            Self::Estimated(simulation) => write!(f, "{}", simulation),
         }
     }
 }
+
+/// Writes each value on its own line, with the chance of it coming up,
+/// i.e. `7  16.67%`.
+impl fmt::Display for distribution::Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let probabilities = self.probabilities();
+        let width = probabilities
+            .keys()
+            .map(|value| value.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        for (i, (value, probability)) in probabilities.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            write!(
+                f,
+                "{:>width$}  {:>6}",
+                value,
+                Percentage(probability.to_f64()),
+                width = width
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes each value on its own line, with the estimated chance of it coming up
+/// and the range it's likely to be within, i.e. `7  16.52%  (15.81% to 17.26%)`.
+impl fmt::Display for eval::Simulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let estimates = self.estimates();
+        let width = estimates
+            .iter()
+            .map(|(value, _)| value.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        write!(f, "estimated from {} rolls", self.num_samples())?;
+        for (value, estimate) in estimates {
+            write!(
+                f,
+                "\n{:>width$}  {:>6}  ({} to {})",
+                value,
+                Percentage(estimate.probability),
+                Percentage(estimate.low),
+                Percentage(estimate.high),
+                width = width
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A bar chart of how likely each value is, with the value nearest the mean
+/// and the value rolled marked, i.e. `11  12.50%  ########  <- mean 10.50`.
+pub struct Histogram {
+    probabilities: Vec<(i64, f64)>,
+    mean: f64,
+    rolled: Option<i64>,
+}
+
+impl Histogram {
+    pub fn new(odds: &distribution::Odds, rolled: Option<i64>) -> Self {
+        Self {
+            probabilities: odds.probabilities(),
+            mean: odds.stats().mean,
+            rolled,
+        }
+    }
+
+    /// The value closest to the mean.
+    fn nearest_mean(&self) -> Option<i64> {
+        self.probabilities
+            .iter()
+            .map(|&(value, _)| value)
+            .min_by(|a, b| {
+                let a_distance = (*a as f64 - self.mean).abs();
+                let b_distance = (*b as f64 - self.mean).abs();
+                a_distance.total_cmp(&b_distance)
+            })
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let highest = self
+            .probabilities
+            .iter()
+            .map(|&(_, probability)| probability)
+            .fold(0.0, f64::max);
+        let width = self
+            .probabilities
+            .iter()
+            .map(|(value, _)| value.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let nearest_mean = self.nearest_mean();
+
+        for (i, &(value, probability)) in self.probabilities.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            let percentage = Percentage(probability);
+            write!(f, "{:>width$}  {:>6}  ", value, percentage, width = width)?;
+
+            let bar = bar(probability, highest, HISTOGRAM_WIDTH);
+            let is_rolled = self.rolled == Some(value);
+            let is_mean = nearest_mean == Some(value);
+            let marker = match (is_rolled, is_mean) {
+                (true, true) => format!("rolled, mean {:.2}", self.mean),
+                (true, false) => "rolled".to_string(),
+                (false, true) => format!("mean {:.2}", self.mean),
+                (false, false) => String::new(),
+            };
+
+            // Bars are only padded out when there's a marker to line up after them.
+            if marker.is_empty() {
+                write!(f, "{}", bar)?;
+            } else {
+                write!(f, "{:<width$}  <- {}", bar, marker, width = HISTOGRAM_WIDTH)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Two bar charts side by side, with each value on one line,
+/// i.e. `7  16.67%  #####                 8.33%  ##`.
+pub struct VersusChart<'a> {
+    left_label: &'a str,
+    right_label: &'a str,
+    left: BTreeMap<i64, f64>,
+    right: BTreeMap<i64, f64>,
+}
+
+impl<'a> VersusChart<'a> {
+    pub fn new(versus: &distribution::Versus, left_label: &'a str, right_label: &'a str) -> Self {
+        Self {
+            left_label,
+            right_label,
+            left: versus.left.probabilities().into_iter().collect(),
+            right: versus.right.probabilities().into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> fmt::Display for VersusChart<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = self
+            .left
+            .keys()
+            .chain(self.right.keys())
+            .copied()
+            .collect::<BTreeSet<i64>>();
+        let width = values
+            .iter()
+            .map(|value| value.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        // Both sides share a scale, so their bars can be compared.
+        let highest = self
+            .left
+            .values()
+            .chain(self.right.values())
+            .copied()
+            .fold(0.0, f64::max);
+        let bar_width = HISTOGRAM_WIDTH / 2;
+
+        write!(
+            f,
+            "{:width$}  {:<column_width$}  {}",
+            "",
+            self.left_label,
+            self.right_label,
+            width = width,
+            column_width = bar_width + 8
+        )?;
+        for value in values {
+            let left = self.left.get(&value).copied().unwrap_or(0.0);
+            let right = self.right.get(&value).copied().unwrap_or(0.0);
+
+            write!(
+                f,
+                "\n{:>width$}  {:>6}  {:<bar_width$}  {:>6}  {}",
+                value,
+                Percentage(left),
+                bar(left, highest, bar_width),
+                Percentage(right),
+                bar(right, highest, bar_width),
+                width = width,
+                bar_width = bar_width
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Bars are scaled so the most likely value fills the width. Any value
+/// which can come up gets at least a sliver, so it doesn't look impossible.
+fn bar(probability: f64, highest: f64, width: usize) -> String {
+    let length = if highest > 0.0 {
+        (probability / highest * width as f64).round() as usize
+    } else {
+        0
+    };
+    let length = if probability > 0.0 {
+        length.max(1)
+    } else {
+        length
+    };
+
+    "#".repeat(length)
+}
+
+/// Probabilities are written as a percentage, to two decimal places.
+struct Percentage(f64);
+impl fmt::Display for Percentage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Percentage(probability) = self;
+        let percentage = format!("{:.2}%", probability * 100.0);
+
+        f.pad(&percentage)
+    }
+}
+
+impl fmt::Display for distribution::Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min {}, max {}, mean {:.2}, median {}, mode {}, std dev {:.2}",
+            self.min, self.max, self.mean, self.median, self.mode, self.std_dev
+        )?;
+
+        match self.num_samples {
+            Some(num_samples) => write!(f, ", estimated from {} rolls", num_samples),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for distribution::Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Eval(err) => write!(f, "{}", err),
+            Self::NotExact(modifier) => {
+                write!(f, "the odds of `{}` can't be worked out exactly", modifier)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::eval::Limits;
+
+    #[test]
+    fn it_should_scale_histogram_bars_and_mark_the_rolled_value_and_mean() {
+        let histogram = test_histogram("2d4", Some(7));
+        let expected = [
+            format!("2   6.25%  {}", "#".repeat(10)),
+            format!("3  12.50%  {}", "#".repeat(20)),
+            format!("4  18.75%  {}", "#".repeat(30)),
+            format!("5  25.00%  {}  <- mean 5.00", "#".repeat(40)),
+            format!("6  18.75%  {}", "#".repeat(30)),
+            format!("7  12.50%  {:<40}  <- rolled", "#".repeat(20)),
+            format!("8   6.25%  {}", "#".repeat(10)),
+        ];
+
+        assert_eq!(format!("{}", histogram), expected.join("\n"));
+    }
+
+    #[test]
+    fn it_should_mark_a_rolled_mean_once() {
+        let histogram = test_histogram("1d2 + 1d2", Some(3));
+        let expected = [
+            format!("2  25.00%  {}", "#".repeat(20)),
+            format!("3  50.00%  {}  <- rolled, mean 3.00", "#".repeat(40)),
+            format!("4  25.00%  {}", "#".repeat(20)),
+        ];
+
+        assert_eq!(format!("{}", histogram), expected.join("\n"));
+    }
+
+    #[test]
+    fn it_should_give_unlikely_values_a_sliver_of_a_bar() {
+        assert_eq!(bar(0.001, 0.5, HISTOGRAM_WIDTH), "#");
+        assert_eq!(bar(0.0, 0.5, HISTOGRAM_WIDTH), "");
+        assert_eq!(bar(0.25, 0.5, HISTOGRAM_WIDTH), "#".repeat(20));
+    }
+
+    #[test]
+    fn it_should_name_fate_values_on_the_ladder() {
+        let tests = [
+            (-5, "Horrifying (-5)"),
+            (-4, "Horrifying (-4)"),
+            (-1, "Poor (-1)"),
+            (0, "Mediocre (+0)"),
+            (2, "Fair (+2)"),
+            (7, "Epic (+7)"),
+            (8, "Legendary (+8)"),
+            (12, "Legendary (+12)"),
+        ];
+
+        for (n, expected) in tests {
+            assert_eq!(format!("{}", eval::Value::Fate(n)), expected);
+        }
+    }
+
+    #[test]
+    fn it_should_place_a_caret_under_a_parse_error() {
+        let err = parse::parse("(1d6 + 2").unwrap_err();
+        let expected = [
+            "a `(` is never closed, expected `)`",
+            "(1d6 + 2",
+            "        ^",
+            "did you mean `(1d6 + 2)`?",
+        ];
+
+        assert_eq!(format!("{}", err), expected.join("\n"));
+    }
+
+    #[test]
+    fn it_should_size_a_caret_by_characters_rather_than_bytes() {
+        let err = parse::parse("1d6 ×").unwrap_err();
+        let expected = [
+            "unexpected `×`, expected an operator, `,` or the end",
+            "1d6 ×",
+            "    ^",
+            "did you mean `1d6`?",
+        ];
+
+        assert_eq!(format!("{}", err), expected.join("\n"));
+    }
+
+    #[test]
+    fn it_should_say_how_many_rolls_estimated_stats_are_from() {
+        let ast = parse::parse_expr("3d6!").unwrap();
+        let stats = distribution::stats(&ast, &Limits::default()).unwrap();
+
+        assert!(format!("{}", stats).ends_with(", estimated from 10000 rolls"));
+        assert!(format!("{}", stats).starts_with("min 3, max 1815, "));
+    }
+
+    #[test]
+    fn it_should_place_a_caret_under_a_die_without_faces() {
+        let err = parse::parse("d{}").unwrap_err();
+        let expected = ["a die needs at least one face", "d{}", " ^"];
+
+        assert_eq!(format!("{}", err), expected.join("\n"));
+    }
+
+    fn test_histogram(expr: &str, rolled: Option<i64>) -> Histogram {
+        let ast = parse::parse_expr(expr).unwrap();
+        let odds = distribution::odds(&ast, &Limits::default()).unwrap();
+
+        Histogram::new(&odds, rolled)
+    }
+}
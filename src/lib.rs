@@ -1,16 +1,165 @@
+//! Parses and rolls dice expressions, such as `4d6kh3` or `1d20 + 5 >= 15`.
+//!
+//! The functions at the top level write a sentence describing the roll, for
+//! the command line and chat. To roll expressions programmatically, parse them
+//! and then roll them with an [`Eval`], which gives back each value along with
+//! how it was worked out.
+//!
+//! ```
+//! use dice_roll::{parse, Eval, Value};
+//!
+//! let mut eval = Eval::new_with_seed(42);
+//! for ast in parse("2d6 + 3, 1d20").unwrap() {
+//!     let output = eval.eval(ast).unwrap();
+//!     assert!(matches!(output.value, Value::Integer(_)));
+//!     assert_eq!(output.rolls().len(), 1);
+//! }
+//! ```
 #![feature(box_syntax, box_patterns)]
+#![warn(missing_docs)]
 
 use ::std::io;
 
 mod ast;
+mod distribution;
 mod eval;
 mod format;
 mod parse;
 
+pub use self::ast::{Comparison, Condition, Expr, ExprKind, ExprOp, RollModifier, Span, UnaryOp};
+pub use self::eval::Error as EvalError;
+pub use self::eval::{Arity, ErrorKind, Limit, Limits};
+pub use self::eval::{Chance, Estimate, Output, Roll, Sides, Value};
+pub use self::eval::{DiceSource, Eval, Scripted};
+pub use self::format::Markup;
+pub use self::parse::Error as ParseError;
+pub use self::parse::{parse, parse_expr};
+
+/// Parses the input, and rolls each expression in it.
+///
+/// Only a failure to parse stops everything, as each expression is rolled
+/// on its own and can fail without the others doing so.
+pub fn roll(eval: &mut Eval, input: &str) -> Result<Vec<Result<Output, EvalError>>, ParseError> {
+    parse::parse(input).map(|asts| asts.into_iter().map(|ast| eval.eval(ast)).collect())
+}
+
+/// Rolls the input, and writes it along with what each expression came out as.
 pub fn main(username: &str, input: &str, output: &mut impl io::Write) -> io::Result<()> {
-    let mut eval = eval::Eval::new();
-    let result = parse::parse(&input)
-        .map(|asts| return asts.into_iter().map(|ast| eval.eval(ast)).collect());
+    main_with_limits(username, input, output, Limits::default())
+}
+
+/// Like [`main`], but with the limits given.
+pub fn main_with_limits(
+    username: &str,
+    input: &str,
+    output: &mut impl io::Write,
+    limits: Limits,
+) -> io::Result<()> {
+    let result = roll(&mut Eval::new_with_limits(limits), input);
 
     format::fmt(output, &username, &input, &result)
 }
+
+/// Like [`odds_with_limits`], but with the default limits.
+pub fn odds(username: &str, input: &str, output: &mut impl io::Write) -> io::Result<()> {
+    odds_with_limits(username, input, output, Limits::default())
+}
+
+/// Writes the odds of each value the input can come out as, rather than rolling it.
+pub fn odds_with_limits(
+    username: &str,
+    input: &str,
+    output: &mut impl io::Write,
+    limits: Limits,
+) -> io::Result<()> {
+    let result = parse::parse(input).map(|asts| {
+        asts.iter()
+            .map(|ast| distribution::odds(ast, &limits))
+            .collect()
+    });
+
+    format::fmt_odds(output, username, input, &result)
+}
+
+/// Like [`stats_with_limits`], but with the default limits.
+pub fn stats(username: &str, input: &str, output: &mut impl io::Write) -> io::Result<()> {
+    stats_with_limits(username, input, output, Limits::default())
+}
+
+/// Writes a summary of the odds of the input, such as its average.
+pub fn stats_with_limits(
+    username: &str,
+    input: &str,
+    output: &mut impl io::Write,
+    limits: Limits,
+) -> io::Result<()> {
+    let result = parse::parse(input).map(|asts| {
+        asts.iter()
+            .map(|ast| distribution::stats(ast, &limits))
+            .collect()
+    });
+
+    format::fmt_stats(output, username, input, &result)
+}
+
+/// Like [`histogram_with_limits`], but with the default limits, as plain text.
+pub fn histogram(username: &str, input: &str, output: &mut impl io::Write) -> io::Result<()> {
+    histogram_with_limits(username, input, output, Limits::default(), Markup::Plain)
+}
+
+/// Rolls the input, and writes it along with a chart of how likely each value was.
+pub fn histogram_with_limits(
+    username: &str,
+    input: &str,
+    output: &mut impl io::Write,
+    limits: Limits,
+    markup: Markup,
+) -> io::Result<()> {
+    let mut eval = Eval::new_with_limits(limits.clone());
+    let result = parse::parse(input).map(|asts| {
+        asts.into_iter()
+            .map(|ast| {
+                let odds = distribution::odds(&ast, &limits)?;
+                let rolled = eval.eval(ast)?;
+
+                Ok((rolled, odds))
+            })
+            .collect()
+    });
+
+    format::fmt_histogram(output, username, input, &result, markup)
+}
+
+/// Like [`compare_with_limits`], but with the default limits, as plain text.
+pub fn compare(
+    username: &str,
+    left: &str,
+    right: &str,
+    output: &mut impl io::Write,
+) -> io::Result<()> {
+    compare_with_limits(
+        username,
+        left,
+        right,
+        output,
+        Limits::default(),
+        Markup::Plain,
+    )
+}
+
+/// Writes the odds of two expressions side by side, along with how often
+/// each comes out higher than the other.
+pub fn compare_with_limits(
+    username: &str,
+    left: &str,
+    right: &str,
+    output: &mut impl io::Write,
+    limits: Limits,
+    markup: Markup,
+) -> io::Result<()> {
+    let result = parse::parse_expr(left)
+        .and_then(|left_ast| Ok((left_ast, parse::parse_expr(right)?)))
+        .map(|(left_ast, right_ast)| distribution::versus(&left_ast, &right_ast, &limits));
+
+    format::fmt_versus(output, username, left, right, &result, markup)
+}
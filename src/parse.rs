@@ -1,96 +1,337 @@
-use crate::ast::Expr;
-use ::pom;
+use crate::ast::{Expr, ExprKind, ExprOp, RollModifier, Span, UnaryOp};
 use ::pom::parser::*;
+use ::std::cell::RefCell;
+use ::std::rc::Rc;
 
+mod error;
+mod modifier;
 mod number;
 mod op;
+mod sides;
 mod space;
 
-pub fn parse<'a>(expression: &'a str) -> Result<Vec<Expr>, pom::Error> {
+pub use self::error::Error;
+
+/// How deeply expressions may be nested inside each other, such as in
+/// brackets, before parsing them would run out of stack.
+const MAX_DEPTH: usize = 100;
+
+/// Parses a list of expressions separated by commas, i.e. `1d20 + 5, 2d6`.
+pub fn parse(expression: &str) -> Result<Vec<Expr>, Error> {
+    let bytes = expression.as_bytes();
+    let state = State::default();
+    let exprs = exprs(&state)
+        .parse(bytes)
+        .map_err(|err| state.error(expression, err))?;
+
+    // `>` counts successes straight after a roll, but compares otherwise, so
+    // only comparing is allowed in a chance to stop spaces changing its odds.
+    for expr in &exprs {
+        if let ExprKind::Chance(condition) = &expr.kind {
+            if let Some(modifiers) = counting_successes(condition) {
+                return Err(Error::successes_in_chance(expression, modifiers));
+            }
+        }
+    }
+
+    Ok(exprs)
+}
+
+/// Parses exactly one expression, such as either side of a comparison
+/// between two rolls.
+pub fn parse_expr(expression: &str) -> Result<Expr, Error> {
     let bytes = expression.as_bytes();
-    let parser = exprs();
-    parser.parse(&bytes)
+    let state = State::default();
+    let parser = space::optional() * nested(&state, expr_0) - space::optional() - end();
+    parser
+        .parse(bytes)
+        .map_err(|err| state.error(expression, err))
+}
+
+/// Shared by the parsers of one input, to keep hold of errors which no other
+/// way of parsing could get around, such as `d{}` having no faces. Pom drops
+/// these when it tries the other ways, and gives where it stopped instead.
+#[derive(Clone, Default)]
+struct State {
+    /// How deeply nested the expression being parsed is.
+    depth: usize,
+
+    /// The first such error, along with where it was.
+    error: Rc<RefCell<Option<(String, usize)>>>,
+}
+
+impl State {
+    /// Keeps hold of the error, unless there's one already, and gives it back
+    /// as one which stops pom from trying any other way of parsing.
+    fn fail(&self, message: &str, position: usize) -> pom::Error {
+        self.error
+            .borrow_mut()
+            .get_or_insert_with(|| (message.to_string(), position));
+
+        let inner = pom::Error::Conversion {
+            message: message.to_string(),
+            position,
+        };
+        pom::Error::Expect {
+            message: message.to_string(),
+            position,
+            inner: box inner,
+        }
+    }
+
+    fn error(&self, input: &str, err: pom::Error) -> Error {
+        match self.error.borrow_mut().take() {
+            Some((message, position)) => Error::at(input, position, message),
+            None => Error::new(input, err),
+        }
+    }
+}
+
+/// Like `convert`, except the error is kept, as the input can't be parsed
+/// any other way.
+fn convert_or_fail<'a, O: 'a, U: 'a>(
+    state: &State,
+    parser: Parser<'a, u8, O>,
+    f: impl Fn(O) -> Result<U, &'static str> + 'a,
+) -> Parser<'a, u8, U> {
+    let state = state.clone();
+    Parser::new(move |input: &'a [u8], start: usize| {
+        let (output, end) = (parser.method)(input, start)?;
+        match f(output) {
+            Ok(output) => Ok((output, end)),
+            Err(message) => Err(state.fail(message, start)),
+        }
+    })
 }
 
-fn exprs<'a>() -> Parser<'a, u8, Vec<Expr>> {
-    let exprs_list = list(call(expr_0), space::comma());
+/// Parses an expression inside another, such as in brackets, unless it's
+/// nested too deeply. Like `call`, it's only built when needed, as the
+/// grammar is recursive.
+fn nested<'a>(state: &State, parser: fn(&State) -> Parser<'a, u8, Expr>) -> Parser<'a, u8, Expr> {
+    let state = State {
+        depth: state.depth + 1,
+        ..state.clone()
+    };
+
+    Parser::new(move |input: &'a [u8], start: usize| {
+        if state.depth > MAX_DEPTH {
+            let message = format!("too deeply nested, at most {} deep", MAX_DEPTH);
+            return Err(state.fail(&message, start));
+        }
+
+        (parser(&state).method)(input, start)
+    })
+}
+
+/// Whether the input parses, without working out why not if it doesn't.
+fn parses(input: &str) -> bool {
+    exprs(&State::default()).parse(input.as_bytes()).is_ok()
+}
+
+fn exprs<'a>(state: &State) -> Parser<'a, u8, Vec<Expr>> {
+    let exprs_list = list(chance(state) | nested(state, expr_0), space::comma());
     space::optional() * exprs_list - end()
 }
 
-fn expr_0<'a>() -> Parser<'a, u8, Expr> {
-    add_sub() | expr_1()
+/// Asks for the chance of an expression, rather than rolling it,
+/// as either `P(1d20 >= 15)` or `chance 1d20 >= 15`.
+fn chance<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    let probability =
+        sym(b'P') * space::optional() * sym(b'(') * space::optional() * nested(state, expr_0)
+            - space::optional()
+            - sym(b')');
+    let chance = seq(b"chance") * space::required() * nested(state, expr_0);
+
+    spanned((probability | chance).map(|condition| ExprKind::Chance(box condition)))
+}
+
+fn expr_0<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    compare(state)
 }
 
-fn add_sub<'a>() -> Parser<'a, u8, Expr> {
-    let parser = (expr_1() - space::optional() + op::add_sub() - space::optional()) + call(expr_0);
-    parser.map(|((left, op), right)| Expr::Operator(op, box left, box right))
+/// Comparisons don't group, so `1 < 2 < 3` isn't allowed.
+fn compare<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    let parser = add_sub(state) + (operator(op::compare()) + add_sub(state)).opt();
+    parser.map(|(left, maybe_right)| match maybe_right {
+        Some((op, right)) => operator_expr(op, left, right),
+        None => left,
+    })
+}
+
+/// `+` and `-` group to the left, so `10 - 2 - 3` is `(10 - 2) - 3`.
+fn add_sub<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    let parser = expr_1(state) + (operator(op::add_sub()) + expr_1(state)).repeat(0..);
+    parser.map(fold_left)
 }
 
-fn expr_1<'a>() -> Parser<'a, u8, Expr> {
-    mult_div() | expr_2()
+fn expr_1<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    mult_div(state)
+}
+
+/// `*` and `/` group to the left, so `100 / 10 / 2` is `(100 / 10) / 2`.
+fn mult_div<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    let parser = expr_2(state) + (operator(op::mult_div()) + expr_2(state)).repeat(0..);
+    parser.map(fold_left)
+}
+
+fn expr_2<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    unary(state)
+}
+
+/// `-` and `+` bind more loosely than `^`, so `-2 ^ 2` is `-(2 ^ 2)`,
+/// and more tightly than the others, so `1 - -2` is `1 - (-2)`.
+fn unary<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    let parser = signs() + pow(state);
+    parser.map(apply_signs)
+}
+
+/// Any number of signs, along with where each starts, i.e. the `--` in `--3`.
+/// The sign of the lowest number is left to be parsed along with it.
+fn signs<'a>() -> Parser<'a, u8, Vec<(usize, UnaryOp)>> {
+    let sign = !number::minimum() * op::unary() - space::optional();
+    (empty().pos() + sign).repeat(0..)
+}
+
+/// The sign nearest the expression goes innermost, so `-+3` is `-(+3)`.
+fn apply_signs((signs, expr): (Vec<(usize, UnaryOp)>, Expr)) -> Expr {
+    signs.into_iter().rev().fold(expr, |expr, (start, op)| {
+        let span = Span::new(start, expr.span.end);
+        Expr::new(ExprKind::Unary(op, box expr), span)
+    })
+}
+
+/// `^` groups to the right, so `2 ^ 3 ^ 2` is `2 ^ (3 ^ 2)`.
+fn pow<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    let parser = expr_3(state) + (operator(op::power()) + nested(state, expr_2)).opt();
+    parser.map(|(left, maybe_right)| match maybe_right {
+        Some((op, right)) => operator_expr(op, left, right),
+        None => left,
+    })
 }
 
-fn mult_div<'a>() -> Parser<'a, u8, Expr> {
-    let parser = (expr_2() - space::optional() + op::mult_div() - space::optional()) + call(expr_1);
-    parser.map(|((left, op), right)| Expr::Operator(op, box left, box right))
+fn operator<'a>(op: Parser<'a, u8, ExprOp>) -> Parser<'a, u8, ExprOp> {
+    space::optional() * op - space::optional()
 }
 
-fn expr_2<'a>() -> Parser<'a, u8, Expr> {
-    pow() | expr_3()
+fn fold_left((first, rest): (Expr, Vec<(ExprOp, Expr)>)) -> Expr {
+    rest.into_iter()
+        .fold(first, |left, (op, right)| operator_expr(op, left, right))
 }
 
-fn pow<'a>() -> Parser<'a, u8, Expr> {
-    let parser = (expr_3() - space::optional() + op::power() - space::optional()) + call(expr_2);
-    parser.map(|((left, op), right)| Expr::Operator(op, box left, box right))
+/// An operator covers both of its sides, and everything in between.
+fn operator_expr(op: ExprOp, left: Expr, right: Expr) -> Expr {
+    let span = Span::new(left.span.start, right.span.end);
+    Expr::new(ExprKind::Operator(op, box left, box right), span)
 }
 
-fn expr_3<'a>() -> Parser<'a, u8, Expr> {
-    roll() | expr_4()
+/// Keeps where in the input the expression was parsed from.
+fn spanned<'a>(parser: Parser<'a, u8, ExprKind>) -> Parser<'a, u8, Expr> {
+    let parser = empty().pos() + parser + empty().pos();
+    parser.map(|((start, kind), end)| Expr::new(kind, Span::new(start, end)))
 }
 
-fn roll<'a>() -> Parser<'a, u8, Expr> {
-    let parser = (expr_4().opt() + op::roll() - space::optional()) + call(expr_3);
-    parser.map(|((maybe_left, op), right)| {
-        let left = maybe_left.unwrap_or(Expr::Integer(1));
-        Expr::Operator(op, box left, box right)
+/// The number of dice is parsed once, followed by the rest of the roll if
+/// there is one, as parsing it again each time would take exponentially long
+/// for nested brackets.
+fn expr_3<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    let with_num_dice = expr_4(state) + roll(state).opt();
+    let without_num_dice = empty().pos() + roll(state);
+
+    with_num_dice.map(|(left, maybe_roll)| match maybe_roll {
+        Some(roll) => roll_expr(left, roll),
+        None => left,
+    }) | without_num_dice.map(|(start, roll)| {
+        // A roll without a number of dice, like `d6`, rolls one die.
+        let num_dice = Expr::new(ExprKind::Integer(1), Span::new(start, start));
+        roll_expr(num_dice, roll)
     })
 }
 
-fn expr_4<'a>() -> Parser<'a, u8, Expr> {
-    expr_with_brackets() | number::number()
+/// The `d` of a roll and everything after it, along with where it ends.
+fn roll<'a>(state: &State) -> Parser<'a, u8, ((Expr, Vec<RollModifier>), usize)> {
+    (op::roll() - space::optional()) * roll_sides(state) + modifier::modifiers() + empty().pos()
+}
+
+fn roll_expr(left: Expr, ((right, modifiers), end): ((Expr, Vec<RollModifier>), usize)) -> Expr {
+    let span = Span::new(left.span.start, end);
+    Expr::new(ExprKind::Operator(ExprOp::Roll(modifiers), box left, box right), span)
+}
+
+/// Sides can have a sign, like `1d-6`, without needing brackets.
+fn roll_sides<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    let sides =
+        spanned(sides::fudge() | sides::percentile() | sides::faces(state)) | nested(state, expr_3);
+    let parser = signs() + sides;
+    parser.map(apply_signs)
+}
+
+fn expr_4<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    function_call(state) | expr_with_brackets(state) | spanned(number::number())
+}
+
+/// A call to a function by name, i.e. `max(1, 1d4 - 1)`. There's no
+/// function called `d`, so `d(6)` is left to be parsed as a roll.
+fn function_call<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    let roll = op::roll() - sym(b'(');
+    let name = is_a(|c: u8| c.is_ascii_lowercase())
+        .repeat(1..)
+        .convert(String::from_utf8);
+    let arg = space::optional() * nested(state, expr_0) - space::optional();
+    let args = sym(b'(') * space::optional() * list(arg, sym(b',')) - sym(b')');
+
+    spanned((!roll * name + args).map(|(name, args)| ExprKind::Call(name, args)))
+}
+
+/// Where the modifiers are, of the first roll which counts successes.
+fn counting_successes(expr: &Expr) -> Option<Span> {
+    match &expr.kind {
+        ExprKind::Operator(ExprOp::Roll(modifiers), _, sides)
+            if modifiers
+                .iter()
+                .any(|modifier| matches!(modifier, RollModifier::Success(_))) =>
+        {
+            Some(Span::new(sides.span.end, expr.span.end))
+        }
+        ExprKind::Operator(_, left, right) => {
+            counting_successes(left).or_else(|| counting_successes(right))
+        }
+        ExprKind::Unary(_, expr) | ExprKind::Chance(expr) => counting_successes(expr),
+        ExprKind::Call(_, args) => args.iter().find_map(counting_successes),
+        ExprKind::Integer(_) | ExprKind::Fudge | ExprKind::Faces(_) => None,
+    }
 }
 
-fn expr_with_brackets<'a>() -> Parser<'a, u8, Expr> {
-    sym(b'(') * space::optional() * call(expr_0) - space::optional() - sym(b')')
+/// Brackets are kept as part of the span of the expression inside them.
+fn expr_with_brackets<'a>(state: &State) -> Parser<'a, u8, Expr> {
+    let parser = empty().pos()
+        + (sym(b'(') * space::optional() * nested(state, expr_0) - space::optional() - sym(b')'))
+        + empty().pos();
+    parser.map(|((start, expr), end)| Expr::new(expr.kind, Span::new(start, end)))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::ast::ExprOp;
+    use crate::ast::{Comparison, Condition};
 
     #[test]
     fn it_should_parse_number_zero() {
-        test_single(&"0", Expr::Integer(0));
+        test_single("0", Expr::integer(0));
     }
 
     #[test]
     fn it_should_parse_number() {
-        test_single(&"1", Expr::Integer(1));
-        test_single(&"123", Expr::Integer(123));
-        test_single(&"900", Expr::Integer(900));
-        test_single(&"6594", Expr::Integer(6594));
+        test_single("1", Expr::integer(1));
+        test_single("123", Expr::integer(123));
+        test_single("900", Expr::integer(900));
+        test_single("6594", Expr::integer(6594));
     }
 
     #[test]
     fn it_should_add_two_numbers_with_no_spaces() {
         test_single(
             &"1+2",
-            Expr::Operator(
-                ExprOp::Add,
-                Box::new(Expr::Integer(1)),
-                Box::new(Expr::Integer(2)),
-            ),
+            Expr::operator(ExprOp::Add, Expr::integer(1), Expr::integer(2)),
         )
     }
 
@@ -98,11 +339,7 @@ mod test {
     fn it_should_add_two_numbers_with_spaces() {
         test_single(
             &"1 + 2",
-            Expr::Operator(
-                ExprOp::Add,
-                Box::new(Expr::Integer(1)),
-                Box::new(Expr::Integer(2)),
-            ),
+            Expr::operator(ExprOp::Add, Expr::integer(1), Expr::integer(2)),
         )
     }
 
@@ -110,14 +347,10 @@ mod test {
     fn it_should_add_three_numbers_with_spaces() {
         test_single(
             &"1 + 2 + 3",
-            Expr::Operator(
+            Expr::operator(
                 ExprOp::Add,
-                Box::new(Expr::Integer(1)),
-                Box::new(Expr::Operator(
-                    ExprOp::Add,
-                    Box::new(Expr::Integer(2)),
-                    Box::new(Expr::Integer(3)),
-                )),
+                Expr::operator(ExprOp::Add, Expr::integer(1), Expr::integer(2)),
+                Expr::integer(3),
             ),
         )
     }
@@ -126,67 +359,186 @@ mod test {
     fn it_should_add_and_subtract_lots_of_numbers_with_spaces() {
         test_single(
             &"1 + 2 - 3 + 4 - 5 - 6 + 7 + 8 - 9",
-            Expr::Operator(
-                ExprOp::Add,
-                Box::new(Expr::Integer(1)),
-                Box::new(Expr::Operator(
-                    ExprOp::Sub,
-                    Box::new(Expr::Integer(2)),
-                    Box::new(Expr::Operator(
+            Expr::operator(
+                ExprOp::Sub,
+                Expr::operator(
+                    ExprOp::Add,
+                    Expr::operator(
                         ExprOp::Add,
-                        Box::new(Expr::Integer(3)),
-                        Box::new(Expr::Operator(
+                        Expr::operator(
                             ExprOp::Sub,
-                            Box::new(Expr::Integer(4)),
-                            Box::new(Expr::Operator(
+                            Expr::operator(
                                 ExprOp::Sub,
-                                Box::new(Expr::Integer(5)),
-                                Box::new(Expr::Operator(
+                                Expr::operator(
                                     ExprOp::Add,
-                                    Box::new(Expr::Integer(6)),
-                                    Box::new(Expr::Operator(
-                                        ExprOp::Add,
-                                        Box::new(Expr::Integer(7)),
-                                        Box::new(Expr::Operator(
-                                            ExprOp::Sub,
-                                            Box::new(Expr::Integer(8)),
-                                            Box::new(Expr::Integer(9)),
-                                        )),
-                                    )),
-                                )),
-                            )),
-                        )),
-                    )),
-                )),
+                                    Expr::operator(
+                                        ExprOp::Sub,
+                                        Expr::operator(
+                                            ExprOp::Add,
+                                            Expr::integer(1),
+                                            Expr::integer(2),
+                                        ),
+                                        Expr::integer(3),
+                                    ),
+                                    Expr::integer(4),
+                                ),
+                                Expr::integer(5),
+                            ),
+                            Expr::integer(6),
+                        ),
+                        Expr::integer(7),
+                    ),
+                    Expr::integer(8),
+                ),
+                Expr::integer(9),
             ),
         )
     }
 
+    #[test]
+    fn it_should_subtract_and_divide_from_the_left() {
+        test_single(
+            "10 - 2 - 3",
+            Expr::operator(
+                ExprOp::Sub,
+                Expr::operator(ExprOp::Sub, Expr::integer(10), Expr::integer(2)),
+                Expr::integer(3),
+            ),
+        );
+
+        test_single(
+            "100 / 10 / 2",
+            Expr::operator(
+                ExprOp::Div,
+                Expr::operator(ExprOp::Div, Expr::integer(100), Expr::integer(10)),
+                Expr::integer(2),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_rounding_division_and_modulo() {
+        test_single(
+            "7 // 2 /^ 3 /~ 4 % 5",
+            Expr::operator(
+                ExprOp::Mod,
+                Expr::operator(
+                    ExprOp::RoundDiv,
+                    Expr::operator(
+                        ExprOp::CeilDiv,
+                        Expr::operator(ExprOp::FloorDiv, Expr::integer(7), Expr::integer(2)),
+                        Expr::integer(3),
+                    ),
+                    Expr::integer(4),
+                ),
+                Expr::integer(5),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_raise_powers_from_the_right() {
+        test_single(
+            "2 ^ 3 ^ 2",
+            Expr::operator(
+                ExprOp::Pow,
+                Expr::integer(2),
+                Expr::operator(ExprOp::Pow, Expr::integer(3), Expr::integer(2)),
+            ),
+        )
+    }
+
+    #[test]
+    fn it_should_parse_signs_in_front_of_expressions() {
+        test_single("-3", Expr::unary(UnaryOp::Neg, Expr::integer(3)));
+        test_single("+ 3", Expr::unary(UnaryOp::Pos, Expr::integer(3)));
+        test_single(
+            "--3",
+            Expr::unary(UnaryOp::Neg, Expr::unary(UnaryOp::Neg, Expr::integer(3))),
+        );
+        test_single(
+            "1 - -2",
+            Expr::operator(
+                ExprOp::Sub,
+                Expr::integer(1),
+                Expr::unary(UnaryOp::Neg, Expr::integer(2)),
+            ),
+        );
+        test_single(
+            "-1d6",
+            Expr::unary(
+                UnaryOp::Neg,
+                Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(6)),
+            ),
+        );
+        test_single(
+            "1d-6",
+            Expr::operator(
+                ExprOp::Roll(vec![]),
+                Expr::integer(1),
+                Expr::unary(UnaryOp::Neg, Expr::integer(6)),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_the_lowest_number() {
+        test_single("-9223372036854775808", Expr::integer(i64::MIN));
+        test_single(
+            "--9223372036854775808",
+            Expr::unary(UnaryOp::Neg, Expr::integer(i64::MIN)),
+        );
+        test_single(
+            "1 + -9223372036854775808",
+            Expr::operator(ExprOp::Add, Expr::integer(1), Expr::integer(i64::MIN)),
+        );
+        assert!(parse("-9223372036854775809").is_err());
+    }
+
+    #[test]
+    fn it_should_raise_powers_before_signs() {
+        test_single(
+            "-2 ^ 2",
+            Expr::unary(
+                UnaryOp::Neg,
+                Expr::operator(ExprOp::Pow, Expr::integer(2), Expr::integer(2)),
+            ),
+        );
+        test_single(
+            "2 ^ -1",
+            Expr::operator(
+                ExprOp::Pow,
+                Expr::integer(2),
+                Expr::unary(UnaryOp::Neg, Expr::integer(1)),
+            ),
+        );
+        test_single(
+            "-2 * 3",
+            Expr::operator(
+                ExprOp::Mult,
+                Expr::unary(UnaryOp::Neg, Expr::integer(2)),
+                Expr::integer(3),
+            ),
+        );
+    }
+
     #[test]
     fn it_should_add_handle_operator_precedence_with_mult_and_add() {
         test_single(
             &"1 + 2 * 3",
-            Expr::Operator(
+            Expr::operator(
                 ExprOp::Add,
-                Box::new(Expr::Integer(1)),
-                Box::new(Expr::Operator(
-                    ExprOp::Mult,
-                    Box::new(Expr::Integer(2)),
-                    Box::new(Expr::Integer(3)),
-                )),
+                Expr::integer(1),
+                Expr::operator(ExprOp::Mult, Expr::integer(2), Expr::integer(3)),
             ),
         );
 
         test_single(
             &"1 * 2 + 3",
-            Expr::Operator(
+            Expr::operator(
                 ExprOp::Add,
-                Box::new(Expr::Operator(
-                    ExprOp::Mult,
-                    Box::new(Expr::Integer(1)),
-                    Box::new(Expr::Integer(2)),
-                )),
-                Box::new(Expr::Integer(3)),
+                Expr::operator(ExprOp::Mult, Expr::integer(1), Expr::integer(2)),
+                Expr::integer(3),
             ),
         );
     }
@@ -195,30 +547,22 @@ mod test {
     fn it_should_add_handle_operator_precedence_with_lots_of_numbers() {
         test_single(
             &"1 + 2 * 3 * 4 + 5 * 6 + 7",
-            Expr::Operator(
+            Expr::operator(
                 ExprOp::Add,
-                Box::new(Expr::Integer(1)),
-                Box::new(Expr::Operator(
+                Expr::operator(
                     ExprOp::Add,
-                    Box::new(Expr::Operator(
-                        ExprOp::Mult,
-                        Box::new(Expr::Integer(2)),
-                        Box::new(Expr::Operator(
-                            ExprOp::Mult,
-                            Box::new(Expr::Integer(3)),
-                            Box::new(Expr::Integer(4)),
-                        )),
-                    )),
-                    Box::new(Expr::Operator(
+                    Expr::operator(
                         ExprOp::Add,
-                        Box::new(Expr::Operator(
+                        Expr::integer(1),
+                        Expr::operator(
                             ExprOp::Mult,
-                            Box::new(Expr::Integer(5)),
-                            Box::new(Expr::Integer(6)),
-                        )),
-                        Box::new(Expr::Integer(7)),
-                    )),
-                )),
+                            Expr::operator(ExprOp::Mult, Expr::integer(2), Expr::integer(3)),
+                            Expr::integer(4),
+                        ),
+                    ),
+                    Expr::operator(ExprOp::Mult, Expr::integer(5), Expr::integer(6)),
+                ),
+                Expr::integer(7),
             ),
         )
     }
@@ -227,22 +571,14 @@ mod test {
     fn it_should_handle_brackets() {
         test_single(
             &"(1 + 2) * 3 * (4 + 5)",
-            Expr::Operator(
+            Expr::operator(
                 ExprOp::Mult,
-                Box::new(Expr::Operator(
-                    ExprOp::Add,
-                    Box::new(Expr::Integer(1)),
-                    Box::new(Expr::Integer(2)),
-                )),
-                Box::new(Expr::Operator(
+                Expr::operator(
                     ExprOp::Mult,
-                    Box::new(Expr::Integer(3)),
-                    Box::new(Expr::Operator(
-                        ExprOp::Add,
-                        Box::new(Expr::Integer(4)),
-                        Box::new(Expr::Integer(5)),
-                    )),
-                )),
+                    Expr::operator(ExprOp::Add, Expr::integer(1), Expr::integer(2)),
+                    Expr::integer(3),
+                ),
+                Expr::operator(ExprOp::Add, Expr::integer(4), Expr::integer(5)),
             ),
         )
     }
@@ -252,23 +588,15 @@ mod test {
         test_multiple(
             &"1 + 2 * 3 1 * 2 + 3",
             vec![
-                Expr::Operator(
+                Expr::operator(
                     ExprOp::Add,
-                    Box::new(Expr::Integer(1)),
-                    Box::new(Expr::Operator(
-                        ExprOp::Mult,
-                        Box::new(Expr::Integer(2)),
-                        Box::new(Expr::Integer(3)),
-                    )),
+                    Expr::integer(1),
+                    Expr::operator(ExprOp::Mult, Expr::integer(2), Expr::integer(3)),
                 ),
-                Expr::Operator(
+                Expr::operator(
                     ExprOp::Add,
-                    Box::new(Expr::Operator(
-                        ExprOp::Mult,
-                        Box::new(Expr::Integer(1)),
-                        Box::new(Expr::Integer(2)),
-                    )),
-                    Box::new(Expr::Integer(3)),
+                    Expr::operator(ExprOp::Mult, Expr::integer(1), Expr::integer(2)),
+                    Expr::integer(3),
                 ),
             ],
         );
@@ -279,16 +607,8 @@ mod test {
         test_multiple(
             &"1d6 1d6",
             vec![
-                Expr::Operator(
-                    ExprOp::Roll,
-                    Box::new(Expr::Integer(1)),
-                    Box::new(Expr::Integer(6)),
-                ),
-                Expr::Operator(
-                    ExprOp::Roll,
-                    Box::new(Expr::Integer(1)),
-                    Box::new(Expr::Integer(6)),
-                ),
+                Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(6)),
+                Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(6)),
             ],
         );
     }
@@ -298,16 +618,8 @@ mod test {
         test_multiple(
             &"1d6,1d6",
             vec![
-                Expr::Operator(
-                    ExprOp::Roll,
-                    Box::new(Expr::Integer(1)),
-                    Box::new(Expr::Integer(6)),
-                ),
-                Expr::Operator(
-                    ExprOp::Roll,
-                    Box::new(Expr::Integer(1)),
-                    Box::new(Expr::Integer(6)),
-                ),
+                Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(6)),
+                Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(6)),
             ],
         );
     }
@@ -317,16 +629,8 @@ mod test {
         test_multiple(
             &"1d6 1d6",
             vec![
-                Expr::Operator(
-                    ExprOp::Roll,
-                    Box::new(Expr::Integer(1)),
-                    Box::new(Expr::Integer(6)),
-                ),
-                Expr::Operator(
-                    ExprOp::Roll,
-                    Box::new(Expr::Integer(1)),
-                    Box::new(Expr::Integer(6)),
-                ),
+                Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(6)),
+                Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(6)),
             ],
         );
     }
@@ -335,10 +639,10 @@ mod test {
     fn it_should_handle_one_dice_roll_with_num_dice_omitted() {
         test_multiple(
             &"d6",
-            vec![Expr::Operator(
-                ExprOp::Roll,
-                Box::new(Expr::Integer(1)),
-                Box::new(Expr::Integer(6)),
+            vec![Expr::operator(
+                ExprOp::Roll(vec![]),
+                Expr::integer(1),
+                Expr::integer(6),
             )],
         );
     }
@@ -348,25 +652,556 @@ mod test {
         test_multiple(
             &"d6 d6",
             vec![
-                Expr::Operator(
-                    ExprOp::Roll,
-                    Box::new(Expr::Integer(1)),
-                    Box::new(Expr::Integer(6)),
+                Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(6)),
+                Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(6)),
+            ],
+        );
+    }
+
+    #[test]
+    fn it_should_parse_keep_highest() {
+        test_single(
+            "4d6kh3",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::KeepHighest(3)]),
+                Expr::integer(4),
+                Expr::integer(6),
+            ),
+        );
+
+        test_single(
+            "4d6k3",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::KeepHighest(3)]),
+                Expr::integer(4),
+                Expr::integer(6),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_keep_lowest() {
+        test_single(
+            "2d20kl1",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::KeepLowest(1)]),
+                Expr::integer(2),
+                Expr::integer(20),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_default_keep_count_to_one() {
+        test_single(
+            "2d20kh",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::KeepHighest(1)]),
+                Expr::integer(2),
+                Expr::integer(20),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_keep_within_an_expression() {
+        test_single(
+            "2d20kl1 + 5",
+            Expr::operator(
+                ExprOp::Add,
+                Expr::operator(
+                    ExprOp::Roll(vec![RollModifier::KeepLowest(1)]),
+                    Expr::integer(2),
+                    Expr::integer(20),
                 ),
-                Expr::Operator(
-                    ExprOp::Roll,
-                    Box::new(Expr::Integer(1)),
-                    Box::new(Expr::Integer(6)),
+                Expr::integer(5),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_drop_lowest() {
+        test_single(
+            "4d6dl1",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::DropLowest(1)]),
+                Expr::integer(4),
+                Expr::integer(6),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_drop_highest() {
+        test_single(
+            "5d10dh2",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::DropHighest(2)]),
+                Expr::integer(5),
+                Expr::integer(10),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_drop_after_nested_roll() {
+        test_single(
+            "2d6d8dl1",
+            Expr::operator(
+                ExprOp::Roll(vec![]),
+                Expr::integer(2),
+                Expr::operator(
+                    ExprOp::Roll(vec![RollModifier::DropLowest(1)]),
+                    Expr::integer(6),
+                    Expr::integer(8),
+                ),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_keep_and_drop_together() {
+        test_single(
+            "6d6dl1kh3",
+            Expr::operator(
+                ExprOp::Roll(vec![
+                    RollModifier::DropLowest(1),
+                    RollModifier::KeepHighest(3),
+                ]),
+                Expr::integer(6),
+                Expr::integer(6),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_exploding_dice() {
+        test_single(
+            "3d6!",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::Explode(None)]),
+                Expr::integer(3),
+                Expr::integer(6),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_exploding_dice_with_conditions() {
+        test_single(
+            "3d6!>5",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::Explode(Some(Condition::AtLeast(5)))]),
+                Expr::integer(3),
+                Expr::integer(6),
+            ),
+        );
+
+        test_single(
+            "3d6!<=2",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::Explode(Some(Condition::AtMost(2)))]),
+                Expr::integer(3),
+                Expr::integer(6),
+            ),
+        );
+
+        test_single(
+            "3d6!5",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::Explode(Some(Condition::Equal(5)))]),
+                Expr::integer(3),
+                Expr::integer(6),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_compounding_dice() {
+        test_single(
+            "5d6!!",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::Compound(None)]),
+                Expr::integer(5),
+                Expr::integer(6),
+            ),
+        );
+
+        test_single(
+            "5d6!!>5",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::Compound(Some(Condition::AtLeast(5)))]),
+                Expr::integer(5),
+                Expr::integer(6),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_penetrating_dice() {
+        test_single(
+            "2d6!p",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::Penetrate(None)]),
+                Expr::integer(2),
+                Expr::integer(6),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_rerolls() {
+        test_single(
+            "2d6r1",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::Reroll(Some(Condition::Equal(1)))]),
+                Expr::integer(2),
+                Expr::integer(6),
+            ),
+        );
+
+        test_single(
+            "2d6r<3",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::Reroll(Some(Condition::AtMost(3)))]),
+                Expr::integer(2),
+                Expr::integer(6),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_reroll_once() {
+        test_single(
+            "2d6ro<=2",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::RerollOnce(Some(Condition::AtMost(2)))]),
+                Expr::integer(2),
+                Expr::integer(6),
+            ),
+        );
+
+        test_single(
+            "2d6ro",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::RerollOnce(None)]),
+                Expr::integer(2),
+                Expr::integer(6),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_success_counting() {
+        test_single(
+            "10d10>=7",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::Success(Condition::AtLeast(7))]),
+                Expr::integer(10),
+                Expr::integer(10),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_success_counting_with_failures() {
+        test_single(
+            "6d6>4f1",
+            Expr::operator(
+                ExprOp::Roll(vec![
+                    RollModifier::Success(Condition::AtLeast(4)),
+                    RollModifier::Failure(Condition::Equal(1)),
+                ]),
+                Expr::integer(6),
+                Expr::integer(6),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_fudge_dice() {
+        test_single(
+            "4dF",
+            Expr::operator(ExprOp::Roll(vec![]), Expr::integer(4), Expr::fudge()),
+        );
+
+        test_single(
+            "4dF + 2",
+            Expr::operator(
+                ExprOp::Add,
+                Expr::operator(ExprOp::Roll(vec![]), Expr::integer(4), Expr::fudge()),
+                Expr::integer(2),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_percentile_dice() {
+        test_single(
+            "d%",
+            Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(100)),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_dice_with_custom_faces() {
+        test_single(
+            "2d{1,1,2,3,5,8}",
+            Expr::operator(
+                ExprOp::Roll(vec![]),
+                Expr::integer(2),
+                Expr::faces(vec![1, 1, 2, 3, 5, 8]),
+            ),
+        );
+
+        test_single(
+            "d{ -1, 0, 1 }kh1",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::KeepHighest(1)]),
+                Expr::integer(1),
+                Expr::faces(vec![-1, 0, 1]),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_not_parse_dice_with_no_faces() {
+        assert!(parse("d{}").is_err());
+    }
+
+    #[test]
+    fn it_should_parse_comparisons() {
+        test_single(
+            "1d20 + 5 >= 15",
+            Expr::operator(
+                ExprOp::Compare(Comparison::GreaterOrEqual),
+                Expr::operator(
+                    ExprOp::Add,
+                    Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(20)),
+                    Expr::integer(5),
                 ),
+                Expr::integer(15),
+            ),
+        );
+
+        test_single(
+            "2*3!=6",
+            Expr::operator(
+                ExprOp::Compare(Comparison::NotEqual),
+                Expr::operator(ExprOp::Mult, Expr::integer(2), Expr::integer(3)),
+                Expr::integer(6),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_count_successes_rather_than_compare_without_spaces() {
+        test_single(
+            "1d20>=15",
+            Expr::operator(
+                ExprOp::Roll(vec![RollModifier::Success(Condition::AtLeast(15))]),
+                Expr::integer(1),
+                Expr::integer(20),
+            ),
+        );
+    }
+
+    #[test]
+    fn it_should_parse_chances() {
+        let condition = Expr::operator(
+            ExprOp::Compare(Comparison::Greater),
+            Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(6)),
+            Expr::integer(4),
+        );
+
+        test_single("P(1d6 > 4)", Expr::chance(condition.clone()));
+        test_single("chance 1d6 > 4", Expr::chance(condition.clone()));
+        test_multiple(
+            "P( 1d6 > 4 ), 1d6",
+            vec![
+                Expr::chance(condition),
+                Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(6)),
             ],
         );
     }
 
+    #[test]
+    fn it_should_parse_function_calls() {
+        test_single(
+            "max(1, 1d4-1)",
+            Expr::call(
+                "max",
+                vec![
+                    Expr::integer(1),
+                    Expr::operator(
+                        ExprOp::Sub,
+                        Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(4)),
+                        Expr::integer(1),
+                    ),
+                ],
+            ),
+        );
+        test_single(
+            "abs( -2 )",
+            Expr::call("abs", vec![Expr::unary(UnaryOp::Neg, Expr::integer(2))]),
+        );
+        test_single("min()", Expr::call("min", vec![]));
+        test_single(
+            "d(6)",
+            Expr::operator(ExprOp::Roll(vec![]), Expr::integer(1), Expr::integer(6)),
+        );
+    }
+
+    #[test]
+    fn it_should_not_count_successes_in_chances() {
+        let err = parse("P(2d6>=7)").unwrap_err();
+
+        assert_eq!(err.span, Span::new(5, 8));
+        assert_eq!(err.message, "successes can't be counted in a chance");
+        assert_eq!(err.suggestion, Some("P(2d6 >= 7)".to_string()));
+
+        let err = parse("1d20, chance 4d6kh3>=10 + 1").unwrap_err();
+
+        assert_eq!(err.span, Span::new(16, 23));
+        assert_eq!(err.suggestion, None);
+    }
+
+    #[test]
+    fn it_should_parse_exactly_one_expression() {
+        let expected = Expr::operator(ExprOp::Roll(vec![]), Expr::integer(2), Expr::integer(6));
+
+        assert_eq!(without_spans(parse_expr(" 2d6 ").unwrap()), expected);
+        assert!(parse_expr("2d6, 1d12").is_err());
+    }
+
+    #[test]
+    fn it_should_keep_where_each_expression_came_from() {
+        let input = "2 * (d6 + 1)";
+        let expr = parse_one(input);
+        let (left, right) = match &expr.kind {
+            ExprKind::Operator(_, left, right) => (left, right),
+            kind => panic!("expected an operator, got {:?}", kind),
+        };
+
+        assert_eq!(expr.span.text(input), "2 * (d6 + 1)");
+        assert_eq!(left.span.text(input), "2");
+        assert_eq!(right.span.text(input), "(d6 + 1)");
+    }
+
+    #[test]
+    fn it_should_point_to_where_parsing_failed() {
+        let err = parse("1d20 x").unwrap_err();
+
+        assert_eq!(err.span, Span::new(5, 6));
+        assert_eq!(err.message, "unexpected `x`");
+        assert_eq!(err.expected, vec!["an operator", "`,`", "the end"]);
+        assert_eq!(err.suggestion, Some("1d20".to_string()));
+    }
+
+    #[test]
+    fn it_should_point_past_operators_with_nothing_after_them() {
+        let err = parse("1d20 +").unwrap_err();
+
+        assert_eq!(err.span, Span::new(6, 6));
+        assert_eq!(err.message, "nothing comes after `+`");
+
+        let err = parse("1d20 // x").unwrap_err();
+
+        assert_eq!(err.span, Span::new(8, 9));
+        assert_eq!(err.message, "unexpected `x` after `//`");
+    }
+
+    #[test]
+    fn it_should_suggest_similar_input_which_parses() {
+        let suggest = |expr: &str| parse(expr).unwrap_err().suggestion;
+
+        assert_eq!(suggest("1D20"), Some("1d20".to_string()));
+        assert_eq!(suggest("(1d20 + 2"), Some("(1d20 + 2)".to_string()));
+        assert_eq!(suggest("2d6 */ 3"), Some("2d6 / 3".to_string()));
+        assert_eq!(suggest("1d"), None);
+    }
+
+    #[test]
+    fn it_should_parse_deeply_nested_brackets() {
+        let input = format!("{}1d6{}", "(".repeat(60), ")".repeat(60));
+        assert_eq!(
+            parse(&input).map(|exprs| exprs.into_iter().map(without_spans).collect()),
+            Ok(vec![Expr::operator(
+                ExprOp::Roll(vec![]),
+                Expr::integer(1),
+                Expr::integer(6)
+            )])
+        );
+
+        let input = format!("{}1d6{}", "-(".repeat(60), ")".repeat(60));
+        assert!(parse(&input).is_ok());
+    }
+
+    #[test]
+    fn it_should_not_parse_expressions_nested_too_deeply() {
+        let too_deep = [
+            format!("{}1d6{}", "(".repeat(2_000), ")".repeat(2_000)),
+            format!("{}1", "max(".repeat(2_000)),
+            format!("1{}", "^1".repeat(2_000)),
+            format!("1{}", "d1".repeat(2_000)),
+            format!("P({}1d6{} > 3)", "(".repeat(100), ")".repeat(100)),
+        ];
+
+        for input in &too_deep {
+            let err = parse(input).unwrap_err();
+            assert_eq!(err.message, "too deeply nested, at most 100 deep");
+        }
+
+        let err = parse_expr(&too_deep[0]).unwrap_err();
+        assert_eq!(err.span, Span::new(100, 101));
+    }
+
+    #[test]
+    fn it_should_not_parse_numbers_which_are_too_big() {
+        let err = parse("1d99999999999999999999").unwrap_err();
+
+        assert_eq!(err.span, Span::new(2, 22));
+        assert_eq!(err.message, "`99999999999999999999` is too big a number");
+    }
+
+    #[test]
+    fn it_should_not_parse_dice_without_faces() {
+        let err = parse("1 + 2d{}").unwrap_err();
+
+        assert_eq!(err.span, Span::new(6, 7));
+        assert_eq!(err.message, "a die needs at least one face");
+        assert_eq!(err.expected, Vec::<&str>::new());
+    }
+
+    fn parse_one(expr: &str) -> Expr {
+        parse(expr).unwrap().remove(0)
+    }
+
     fn test_single(expr: &str, expected: Expr) {
         test_multiple(expr, vec![expected])
     }
 
     fn test_multiple(expr: &str, expected: Vec<Expr>) {
-        assert_eq!(parse(expr), Ok(expected))
+        let parsed = parse(expr).map(|exprs| exprs.into_iter().map(without_spans).collect());
+        assert_eq!(parsed, Ok(expected))
+    }
+
+    /// Spans are tested on their own, so the other tests can leave them out.
+    fn without_spans(expr: Expr) -> Expr {
+        let kind = match expr.kind {
+            ExprKind::Operator(op, left, right) => ExprKind::Operator(
+                op,
+                Box::new(without_spans(*left)),
+                Box::new(without_spans(*right)),
+            ),
+            ExprKind::Unary(op, expr) => ExprKind::Unary(op, Box::new(without_spans(*expr))),
+            ExprKind::Call(name, args) => {
+                ExprKind::Call(name, args.into_iter().map(without_spans).collect())
+            }
+            ExprKind::Chance(condition) => ExprKind::Chance(Box::new(without_spans(*condition))),
+            kind => kind,
+        };
+
+        Expr::from(kind)
     }
 }
@@ -0,0 +1,178 @@
+use crate::ast::Span;
+use crate::parse::parses;
+
+use ::pom;
+
+/// What could start an expression.
+const EXPRESSION: &[&str] = &["a number", "a die", "a function", "`(`"];
+
+/// What could follow the `d` of a roll.
+const SIDES: &[&str] = &["a number", "`F`", "`{`"];
+
+/// What could follow a whole expression.
+const AFTER_EXPRESSION: &[&str] = &["an operator", "`,`", "the end"];
+
+/// Where, and why, the input couldn't be parsed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    /// The input which was being parsed.
+    pub input: String,
+
+    /// The bytes of the input which couldn't be parsed. This is empty when
+    /// something was missing, such as at the end of the input.
+    pub span: Span,
+
+    /// What went wrong, i.e. `expected an operator`.
+    pub message: String,
+
+    /// What could have been parsed instead, i.e. `an operator`.
+    pub expected: Vec<&'static str>,
+
+    /// A similar input which does parse, i.e. `1d20` for `1D20`.
+    pub suggestion: Option<String>,
+}
+
+impl Error {
+    /// Pom only gives where parsing stopped, so this looks at the input
+    /// there to work out what went wrong.
+    pub fn new(input: &str, err: pom::Error) -> Self {
+        let position = match err {
+            pom::Error::Mismatch { position, .. }
+            | pom::Error::Conversion { position, .. }
+            | pom::Error::Expect { position, .. }
+            | pom::Error::Custom { position, .. } => position,
+            pom::Error::Incomplete => input.len(),
+        };
+
+        let rest = input.get(position..).unwrap_or("");
+        let start = input.len() - rest.trim_start().len();
+        let rest = &input[start..];
+        let num_unclosed = input.matches('(').count() as i64 - input.matches(')').count() as i64;
+
+        // Parsing stops before an operator, or a `d`, when what follows it is wrong.
+        let (previous, after, expected) = match operator(rest) {
+            Some("d") => (Some("d"), rest[1..].trim_start(), SIDES),
+            Some(op) => (Some(op), rest[op.len()..].trim_start(), EXPRESSION),
+            None if start == 0 => (None, rest, EXPRESSION),
+            None => (None, rest, AFTER_EXPRESSION),
+        };
+        let culprit = input.len() - after.len();
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+        let (span, message, expected) = match (after.chars().next(), previous) {
+            _ if digits > 0 && after[..digits].parse::<i64>().is_err() => (
+                Span::new(culprit, culprit + digits),
+                format!("`{}` is too big a number", &after[..digits]),
+                vec![],
+            ),
+            _ if num_unclosed > 0 => (
+                Span::new(input.len(), input.len()),
+                "a `(` is never closed".to_string(),
+                vec!["`)`"],
+            ),
+            (Some(c), Some(previous)) => (
+                Span::new(culprit, culprit + c.len_utf8()),
+                format!("unexpected `{}` after `{}`", c, previous),
+                expected.to_vec(),
+            ),
+            (Some(c), None) => (
+                Span::new(culprit, culprit + c.len_utf8()),
+                format!("unexpected `{}`", c),
+                expected.to_vec(),
+            ),
+            (None, Some(previous)) => (
+                Span::new(culprit, culprit),
+                format!("nothing comes after `{}`", previous),
+                expected.to_vec(),
+            ),
+            (None, None) => (
+                Span::new(culprit, culprit),
+                "the input ended early".to_string(),
+                expected.to_vec(),
+            ),
+        };
+
+        Self {
+            input: input.to_string(),
+            suggestion: suggest(input, start, num_unclosed),
+            span,
+            message,
+            expected,
+        }
+    }
+
+    /// An error which the input couldn't be parsed around, at the position,
+    /// such as a die with no faces.
+    pub fn at(input: &str, position: usize, message: String) -> Self {
+        let width = input[position..].chars().next().map_or(0, char::len_utf8);
+
+        Self {
+            input: input.to_string(),
+            span: Span::new(position, position + width),
+            message,
+            expected: vec![],
+            suggestion: None,
+        }
+    }
+
+    /// The span is of the modifiers, such as the `>=7` in `P(2d6>=7)`, which
+    /// is suggested with spaces so that it compares the total instead.
+    pub fn successes_in_chance(input: &str, span: Span) -> Self {
+        let modifiers = span.text(input);
+        let number = modifiers.trim_start_matches(|c| "<>=".contains(c));
+        let comparison = &modifiers[..modifiers.len() - number.len()];
+
+        let suggestion = format!(
+            "{} {} {}{}",
+            &input[..span.start],
+            comparison,
+            number,
+            &input[span.end..]
+        );
+        let is_comparison = !comparison.is_empty()
+            && !number.is_empty()
+            && number.chars().all(|c| c.is_ascii_digit());
+
+        Self {
+            input: input.to_string(),
+            span,
+            message: "successes can't be counted in a chance".to_string(),
+            expected: vec![],
+            suggestion: Some(suggestion).filter(|suggestion| is_comparison && parses(suggestion)),
+        }
+    }
+}
+
+/// The operator, or `d`, at the start of the input, i.e. `//` in `// 2`.
+fn operator(input: &str) -> Option<&'static str> {
+    ["//", "/^", "/~", "+", "-", "*", "/", "%", "^", "d"]
+        .into_iter()
+        .find(|op| input.starts_with(op))
+}
+
+/// Tries a few common fixes to the input, and gives back the first which parses.
+/// Dice are never cut out of the input, so `1d` isn't suggested as `1`.
+fn suggest(input: &str, start: usize, num_unclosed: i64) -> Option<String> {
+    let mut candidates = Vec::new();
+
+    if input.contains('D') {
+        candidates.push(input.replace('D', "d"));
+    }
+    if num_unclosed > 0 {
+        candidates.push(format!("{}{}", input, ")".repeat(num_unclosed as usize)));
+    }
+    if let Some(c) = input[start..].chars().next() {
+        if !c.is_alphanumeric() {
+            let end = start + c.len_utf8();
+            candidates.push(format!("{}{}", &input[..start], &input[end..]));
+        }
+    }
+    if !input[start..].contains('d') {
+        candidates.push(input[..start].to_string());
+    }
+
+    candidates
+        .into_iter()
+        .map(|candidate| candidate.trim().to_string())
+        .find(|candidate| !candidate.is_empty() && candidate != input && parses(candidate))
+}
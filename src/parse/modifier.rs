@@ -0,0 +1,94 @@
+use crate::ast::{Condition, RollModifier};
+use ::pom::parser::*;
+
+use super::number;
+
+pub fn modifiers<'a>() -> Parser<'a, u8, Vec<RollModifier>> {
+    modifier().repeat(0..)
+}
+
+fn modifier<'a>() -> Parser<'a, u8, RollModifier> {
+    keep_lowest()
+        | keep_highest()
+        | drop_lowest()
+        | drop_highest()
+        | compound()
+        | penetrate()
+        | explode()
+        | reroll_once()
+        | reroll()
+        | success()
+        | failure()
+}
+
+fn keep_highest<'a>() -> Parser<'a, u8, RollModifier> {
+    let parser = (seq(b"kh").discard() | sym(b'k').discard()) * count();
+    parser.map(RollModifier::KeepHighest)
+}
+
+fn keep_lowest<'a>() -> Parser<'a, u8, RollModifier> {
+    let parser = seq(b"kl") * count();
+    parser.map(RollModifier::KeepLowest)
+}
+
+fn drop_highest<'a>() -> Parser<'a, u8, RollModifier> {
+    let parser = seq(b"dh") * count();
+    parser.map(RollModifier::DropHighest)
+}
+
+fn drop_lowest<'a>() -> Parser<'a, u8, RollModifier> {
+    let parser = seq(b"dl") * count();
+    parser.map(RollModifier::DropLowest)
+}
+
+fn explode<'a>() -> Parser<'a, u8, RollModifier> {
+    let parser = sym(b'!') * condition().opt();
+    parser.map(RollModifier::Explode)
+}
+
+fn compound<'a>() -> Parser<'a, u8, RollModifier> {
+    let parser = seq(b"!!") * condition().opt();
+    parser.map(RollModifier::Compound)
+}
+
+fn penetrate<'a>() -> Parser<'a, u8, RollModifier> {
+    let parser = seq(b"!p") * condition().opt();
+    parser.map(RollModifier::Penetrate)
+}
+
+fn reroll<'a>() -> Parser<'a, u8, RollModifier> {
+    let parser = sym(b'r') * condition().opt();
+    parser.map(RollModifier::Reroll)
+}
+
+fn reroll_once<'a>() -> Parser<'a, u8, RollModifier> {
+    let parser = seq(b"ro") * condition().opt();
+    parser.map(RollModifier::RerollOnce)
+}
+
+fn success<'a>() -> Parser<'a, u8, RollModifier> {
+    comparison().map(RollModifier::Success)
+}
+
+fn failure<'a>() -> Parser<'a, u8, RollModifier> {
+    let parser = sym(b'f') * condition();
+    parser.map(RollModifier::Failure)
+}
+
+fn condition<'a>() -> Parser<'a, u8, Condition> {
+    comparison() | number::unsigned().map(Condition::Equal)
+}
+
+/// A condition which starts with a comparison, such as `>=7` or `=6`.
+fn comparison<'a>() -> Parser<'a, u8, Condition> {
+    let at_least = (seq(b">=") | seq(b">")) * number::unsigned();
+    let at_most = (seq(b"<=") | seq(b"<")) * number::unsigned();
+    let equal = sym(b'=') * number::unsigned();
+
+    at_least.map(Condition::AtLeast) | at_most.map(Condition::AtMost) | equal.map(Condition::Equal)
+}
+
+/// The number of dice a modifier applies to, which defaults to one.
+fn count<'a>() -> Parser<'a, u8, i64> {
+    number::unsigned().opt().map(|maybe_n| maybe_n.unwrap_or(1))
+}
@@ -1,18 +1,34 @@
 use ::pom::parser::*;
 use ::std::str::{self, FromStr};
 
-use crate::ast::Expr;
+use crate::ast::ExprKind;
 
-pub fn number<'a>() -> Parser<'a, u8, Expr> {
-    integer()
+/// Numbers in expressions have no sign, as a `-` in front of them is an operator.
+/// The lowest number is the exception, as it's too big to be negated.
+pub fn number<'a>() -> Parser<'a, u8, ExprKind> {
+    (minimum() | unsigned()).map(ExprKind::Integer)
 }
 
-fn integer<'a>() -> Parser<'a, u8, Expr> {
-    let unsigned_parser = one_of(b"123456789") - one_of(b"0123456789").repeat(0..) | sym(b'0');
-    let signed_parser = sym(b'-').opt() * unsigned_parser;
+/// `-9223372036854775808`, which can only be written along with its sign.
+pub fn minimum<'a>() -> Parser<'a, u8, i64> {
+    (seq(b"-9223372036854775808") - !one_of(b"0123456789")).map(|_| i64::MIN)
+}
+
+pub fn integer<'a>() -> Parser<'a, u8, i64> {
+    let signed_parser = sym(b'-').opt() * unsigned_digits();
     signed_parser
         .collect()
         .convert(str::from_utf8)
-        .convert(|s| i64::from_str(&s))
-        .map(|n| Expr::Integer(n))
+        .convert(i64::from_str)
+}
+
+pub fn unsigned<'a>() -> Parser<'a, u8, i64> {
+    unsigned_digits()
+        .collect()
+        .convert(str::from_utf8)
+        .convert(i64::from_str)
+}
+
+fn unsigned_digits<'a>() -> Parser<'a, u8, u8> {
+    (one_of(b"123456789") - one_of(b"0123456789").repeat(0..)) | sym(b'0')
 }
@@ -1,8 +1,8 @@
-use crate::ast::ExprOp;
+use crate::ast::{Comparison, ExprOp, UnaryOp};
 use ::pom::parser::*;
 
 pub fn mult_div<'a>() -> Parser<'a, u8, ExprOp> {
-    mult() | div()
+    mult() | floor_div() | ceil_div() | round_div() | div() | modulo()
 }
 
 pub fn add_sub<'a>() -> Parser<'a, u8, ExprOp> {
@@ -25,10 +25,46 @@ pub fn div<'a>() -> Parser<'a, u8, ExprOp> {
     sym(b'/').discard().map(|_| ExprOp::Div)
 }
 
+pub fn floor_div<'a>() -> Parser<'a, u8, ExprOp> {
+    seq(b"//").discard().map(|_| ExprOp::FloorDiv)
+}
+
+pub fn ceil_div<'a>() -> Parser<'a, u8, ExprOp> {
+    seq(b"/^").discard().map(|_| ExprOp::CeilDiv)
+}
+
+pub fn round_div<'a>() -> Parser<'a, u8, ExprOp> {
+    seq(b"/~").discard().map(|_| ExprOp::RoundDiv)
+}
+
+pub fn modulo<'a>() -> Parser<'a, u8, ExprOp> {
+    sym(b'%').discard().map(|_| ExprOp::Mod)
+}
+
+pub fn unary<'a>() -> Parser<'a, u8, UnaryOp> {
+    sym(b'-').map(|_| UnaryOp::Neg) | sym(b'+').map(|_| UnaryOp::Pos)
+}
+
 pub fn power<'a>() -> Parser<'a, u8, ExprOp> {
     sym(b'^').discard().map(|_| ExprOp::Pow)
 }
 
-pub fn roll<'a>() -> Parser<'a, u8, ExprOp> {
-    sym(b'd').discard().map(|_| ExprOp::Roll)
+/// Comparisons need spaces after a roll, as `1d20>=15` counts successes
+/// where `1d20 >= 15` compares the total. Chances only allow comparing.
+pub fn compare<'a>() -> Parser<'a, u8, ExprOp> {
+    let comparison = seq(b">=").map(|_| Comparison::GreaterOrEqual)
+        | seq(b"<=").map(|_| Comparison::LessOrEqual)
+        | seq(b"!=").map(|_| Comparison::NotEqual)
+        | seq(b"==").map(|_| Comparison::Equal)
+        | sym(b'=').map(|_| Comparison::Equal)
+        | sym(b'>').map(|_| Comparison::Greater)
+        | sym(b'<').map(|_| Comparison::Less);
+
+    comparison.map(ExprOp::Compare)
+}
+
+/// The `d` of a roll, which must not be confused with the `dl` and `dh`
+/// drop modifiers that can follow it.
+pub fn roll<'a>() -> Parser<'a, u8, ()> {
+    (sym(b'd') - !one_of(b"lh")).discard()
 }
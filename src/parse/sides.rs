@@ -0,0 +1,29 @@
+use ::pom::parser::*;
+
+use crate::ast::ExprKind;
+
+use super::number;
+use super::space;
+use super::{convert_or_fail, State};
+
+pub fn fudge<'a>() -> Parser<'a, u8, ExprKind> {
+    sym(b'F').map(|_| ExprKind::Fudge)
+}
+
+/// `d%` is the same as `d100`.
+pub fn percentile<'a>() -> Parser<'a, u8, ExprKind> {
+    sym(b'%').map(|_| ExprKind::Integer(100))
+}
+
+pub fn faces<'a>(state: &State) -> Parser<'a, u8, ExprKind> {
+    let faces = list(number::integer(), space::comma());
+    let parser = sym(b'{') * space::optional() * faces - space::optional() - sym(b'}');
+
+    convert_or_fail(state, parser, |faces| {
+        if faces.is_empty() {
+            Err("a die needs at least one face")
+        } else {
+            Ok(ExprKind::Faces(faces))
+        }
+    })
+}